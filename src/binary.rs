@@ -0,0 +1,243 @@
+//! A compact binary format for word counts.
+//!
+//! The format is the 8 byte magic `WHISTBN1`, followed by one record per word:
+//! * the length of the word in bytes, as a little-endian `u32`
+//! * the word itself, as UTF-8
+//! * the count, as a little-endian `u64`
+//!
+//! The records are sorted by the bytes of the word, with no word appearing
+//! twice. Because of that, two files can be merged with a single streaming
+//! pass over each (see [`merge`]) without loading either into memory.
+//!
+//! ```
+//! use whist::binary;
+//! let mut a = Vec::new();
+//! binary::write(&mut a, vec![("cat", 2), ("dog", 1)]).unwrap();
+//! let mut b = Vec::new();
+//! binary::write(&mut b, vec![("bird", 4), ("cat", 3)]).unwrap();
+//! let mut merged = Vec::new();
+//! binary::merge(&a[..], &b[..], &mut merged).unwrap();
+//! let counts: Vec<(String, u64)> = binary::read(&merged[..]).map(Result::unwrap).collect();
+//! assert_eq!(counts, [("bird".to_string(), 4), ("cat".to_string(), 5), ("dog".to_string(), 1)]);
+//! ```
+
+use std::{
+  cmp::Reverse,
+  collections::BinaryHeap,
+  convert::TryFrom,
+  io::{self, BufReader, BufWriter, Read, Write},
+};
+
+/// The first bytes of every file in this format.
+pub const MAGIC: &[u8; 8] = b"WHISTBN1";
+
+/// Writes the counts given, which must already be sorted by word with no
+/// repeats.
+///
+/// Counts that are out of order are an [`io::ErrorKind::InvalidInput`] error.
+pub fn write<'a, W: Write>(
+  out: W, counts: impl IntoIterator<Item = (&'a str, u64)>,
+) -> io::Result<()> {
+  let mut out = BufWriter::new(out);
+  out.write_all(MAGIC)?;
+  let mut prev: Option<&str> = None;
+  for (word, count) in counts {
+    if let Some(prev) = prev {
+      if prev >= word {
+        let message = format!("words must be sorted and unique, `{}` came after `{}`", word, prev);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+      }
+    }
+    write_record(&mut out, word, count)?;
+    prev = Some(word);
+  }
+  out.flush()
+}
+
+fn write_record(out: &mut impl Write, word: &str, count: u64) -> io::Result<()> {
+  let len = u32::try_from(word.len())
+    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "word is too long"))?;
+  out.write_all(&len.to_le_bytes())?;
+  out.write_all(word.as_bytes())?;
+  out.write_all(&count.to_le_bytes())
+}
+
+/// Reads the counts back, one record at a time.
+///
+/// The magic is checked on the first call to `next`.
+pub fn read<R: Read>(input: R) -> Reader<R> {
+  Reader { input: BufReader::new(input), checked_magic: false, done: false }
+}
+
+/// An iterator over the records of a binary counts file, made by [`read`].
+pub struct Reader<R> {
+  input: BufReader<R>,
+  checked_magic: bool,
+  done: bool,
+}
+
+impl<R: Read> Reader<R> {
+  fn next_record(&mut self) -> io::Result<Option<(String, u64)>> {
+    if !self.checked_magic {
+      let mut magic = [0; 8];
+      self.input.read_exact(&mut magic)?;
+      if &magic != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a whist binary counts file"));
+      }
+      self.checked_magic = true;
+    }
+    let mut len = [0; 4];
+    // Note(Lokathor): Running out of bytes right at a record boundary is the
+    // normal end of the file, anywhere else it's an error.
+    match self.input.read(&mut len[..1])? {
+      0 => return Ok(None),
+      _ => self.input.read_exact(&mut len[1..])?,
+    }
+    let mut word = vec![0; u32::from_le_bytes(len) as usize];
+    self.input.read_exact(&mut word)?;
+    let word =
+      String::from_utf8(word).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut count = [0; 8];
+    self.input.read_exact(&mut count)?;
+    Ok(Some((word, u64::from_le_bytes(count))))
+  }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+  type Item = io::Result<(String, u64)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let record = self.next_record().transpose();
+    if !matches!(record, Some(Ok(_))) {
+      self.done = true;
+    }
+    record
+  }
+}
+
+/// Merges two binary counts files into a third, adding up the counts of
+/// words that are in both.
+///
+/// Only one record from each input is held in memory at a time.
+pub fn merge(a: impl Read, b: impl Read, out: impl Write) -> io::Result<()> {
+  let mut a = read(a);
+  let mut b = read(b);
+  let mut out = BufWriter::new(out);
+  out.write_all(MAGIC)?;
+  let mut next_a = a.next().transpose()?;
+  let mut next_b = b.next().transpose()?;
+  loop {
+    match (next_a.take(), next_b.take()) {
+      (None, None) => break,
+      (Some((word, count)), None) => {
+        write_record(&mut out, &word, count)?;
+        next_a = a.next().transpose()?;
+      }
+      (None, Some((word, count))) => {
+        write_record(&mut out, &word, count)?;
+        next_b = b.next().transpose()?;
+      }
+      (Some((word_a, count_a)), Some((word_b, count_b))) => {
+        if word_a < word_b {
+          write_record(&mut out, &word_a, count_a)?;
+          next_a = a.next().transpose()?;
+          next_b = Some((word_b, count_b));
+        } else if word_b < word_a {
+          write_record(&mut out, &word_b, count_b)?;
+          next_a = Some((word_a, count_a));
+          next_b = b.next().transpose()?;
+        } else {
+          write_record(&mut out, &word_a, count_a + count_b)?;
+          next_a = a.next().transpose()?;
+          next_b = b.next().transpose()?;
+        }
+      }
+    }
+  }
+  out.flush()
+}
+
+/// Merges any number of binary counts files into one sorted stream of
+/// records, adding up the counts of words that are in more than one.
+///
+/// Only one record from each input is held in memory at a time.
+///
+/// ```
+/// use whist::binary;
+/// let mut inputs = vec![Vec::new(), Vec::new(), Vec::new()];
+/// binary::write(&mut inputs[0], vec![("cat", 2), ("dog", 1)]).unwrap();
+/// binary::write(&mut inputs[1], vec![("bird", 4), ("cat", 3)]).unwrap();
+/// binary::write(&mut inputs[2], vec![("dog", 5)]).unwrap();
+/// let counts: Vec<(String, u64)> =
+///   binary::merge_all(inputs.iter().map(|input| &input[..])).map(Result::unwrap).collect();
+/// assert_eq!(counts, [("bird".to_string(), 4), ("cat".to_string(), 5), ("dog".to_string(), 6)]);
+/// ```
+pub fn merge_all<R: Read>(inputs: impl IntoIterator<Item = R>) -> MergeAll<R> {
+  MergeAll { readers: inputs.into_iter().map(read).collect(), heap: None, done: false }
+}
+
+/// An iterator over the merged records of several binary counts files, made by
+/// [`merge_all`].
+pub struct MergeAll<R> {
+  readers: Vec<Reader<R>>,
+  /// The next record of each reader that isn't used up, along with which
+  /// reader it came from. This is filled in on the first call to `next`.
+  heap: Option<BinaryHeap<Reverse<(String, usize, u64)>>>,
+  done: bool,
+}
+
+impl<R: Read> MergeAll<R> {
+  /// Puts the next record of reader `i` (if any) into the heap.
+  fn refill(&mut self, i: usize) -> io::Result<()> {
+    if let Some((word, count)) = self.readers[i].next().transpose()? {
+      self.heap.get_or_insert_with(BinaryHeap::new).push(Reverse((word, i, count)));
+    }
+    Ok(())
+  }
+
+  fn next_record(&mut self) -> io::Result<Option<(String, u64)>> {
+    if self.heap.is_none() {
+      self.heap = Some(BinaryHeap::with_capacity(self.readers.len()));
+      for i in 0..self.readers.len() {
+        self.refill(i)?;
+      }
+    }
+    let Reverse((word, i, mut count)) = match self.heap.as_mut().and_then(BinaryHeap::pop) {
+      Some(first) => first,
+      None => return Ok(None),
+    };
+    self.refill(i)?;
+    loop {
+      let same_word = match self.heap.as_ref().and_then(BinaryHeap::peek) {
+        Some(Reverse((next_word, _, _))) => *next_word == word,
+        None => false,
+      };
+      if !same_word {
+        break;
+      }
+      if let Some(Reverse((_, i, more))) = self.heap.as_mut().and_then(BinaryHeap::pop) {
+        count += more;
+        self.refill(i)?;
+      }
+    }
+    Ok(Some((word, count)))
+  }
+}
+
+impl<R: Read> Iterator for MergeAll<R> {
+  type Item = io::Result<(String, u64)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let record = self.next_record().transpose();
+    if !matches!(record, Some(Ok(_))) {
+      self.done = true;
+    }
+    record
+  }
+}
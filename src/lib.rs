@@ -0,0 +1,1007 @@
+//! The word counting parts of `whist`, for use as a library.
+//!
+//! Words are split out of the text the same way the `whist` binary does it,
+//! using Unicode word segmentation. Anything that doesn't have at least one
+//! letter or number in it doesn't count as a word.
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+pub mod binary;
+
+use bstr::ByteSlice;
+
+/// Counts up all the words in the text given.
+///
+/// The counting is case sensitive, and each key in the output is an owned
+/// copy of the word. There's no file system access or interning involved.
+///
+/// ```
+/// let counts = whist::count_text("The cat saw the other cat.");
+/// assert_eq!(counts["cat"], 2);
+/// assert_eq!(counts["the"], 1);
+/// assert_eq!(counts["The"], 1);
+/// assert_eq!(counts.len(), 5);
+/// ```
+pub fn count_text(text: &str) -> BTreeMap<String, usize> {
+  let mut counter = WordCounter::new();
+  counter.feed(text);
+  counter.into_counts()
+}
+
+/// Keeps a running count of the words in all the text fed to it.
+///
+/// Like [`count_text`] the counting is case sensitive, but the text can be
+/// given in as many pieces as you like, and the counts so far can be looked at
+/// between pieces.
+///
+/// ```
+/// use whist::WordCounter;
+/// let mut counter = WordCounter::new();
+/// counter.feed("one fish two fish");
+/// assert_eq!(counter.counts().collect::<Vec<_>>(), [("fish", 2), ("one", 1), ("two", 1)]);
+/// counter.feed("red fish blue fish");
+/// assert_eq!(counter.get("fish"), 4);
+/// assert_eq!(counter.get("red"), 1);
+/// assert_eq!(counter.get("green"), 0);
+/// assert_eq!(counter.into_counts().len(), 5);
+/// ```
+///
+/// Use [`WordCounter::builder`] to change or drop words before they're
+/// counted.
+#[derive(Default)]
+pub struct WordCounter {
+  counts: BTreeMap<String, usize>,
+  transform: Option<Box<Transform>>,
+}
+
+/// A function that changes each word before it's counted, or gives `None` to
+/// not count it at all.
+type Transform = dyn for<'w> FnMut(&'w str) -> Option<Cow<'w, str>>;
+
+impl core::fmt::Debug for WordCounter {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("WordCounter")
+      .field("counts", &self.counts)
+      .field("transform", &self.transform.as_ref().map(|_| ".."))
+      .finish()
+  }
+}
+
+/// Sets up a [`WordCounter`] with a custom transform.
+///
+/// ```
+/// use std::borrow::Cow;
+/// use whist::WordCounter;
+/// let mut counter = WordCounter::builder()
+///   .transform(|word| {
+///     if word.len() < 3 {
+///       None
+///     } else if word.chars().any(char::is_uppercase) {
+///       Some(Cow::Owned(word.to_lowercase()))
+///     } else {
+///       Some(Cow::Borrowed(word))
+///     }
+///   })
+///   .build();
+/// counter.feed("The cat and THE dog, a pet.");
+/// assert_eq!(counter.get("the"), 2);
+/// assert_eq!(counter.get("The"), 0);
+/// assert_eq!(counter.get("a"), 0);
+/// assert_eq!(counter.get("dog"), 1);
+/// ```
+#[derive(Default)]
+pub struct WordCounterBuilder {
+  transform: Option<Box<Transform>>,
+}
+
+impl WordCounterBuilder {
+  /// Runs `f` on each word before it's counted.
+  ///
+  /// Giving back `Some` counts that string (which can be the word as is, or
+  /// changed in any way), and giving back `None` skips the word.
+  pub fn transform<F>(mut self, f: F) -> Self
+  where
+    F: for<'w> FnMut(&'w str) -> Option<Cow<'w, str>> + 'static,
+  {
+    self.transform = Some(Box::new(f));
+    self
+  }
+
+  /// Makes the counter.
+  pub fn build(self) -> WordCounter {
+    WordCounter { counts: BTreeMap::new(), transform: self.transform }
+  }
+}
+
+impl WordCounter {
+  /// Makes a counter with no words counted yet.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Starts building a counter with extra settings.
+  pub fn builder() -> WordCounterBuilder {
+    WordCounterBuilder::default()
+  }
+
+  /// Counts up the words in the text.
+  ///
+  /// A word split across two calls is counted as two separate words.
+  pub fn feed(&mut self, text: &str) {
+    for word in words(text) {
+      let word = match &mut self.transform {
+        Some(transform) => match transform(word) {
+          Some(word) => word,
+          None => continue,
+        },
+        None => Cow::Borrowed(word),
+      };
+      // Note(Lokathor): only words we haven't seen before need an allocation.
+      match self.counts.get_mut(word.as_ref()) {
+        Some(count) => *count += 1,
+        None => {
+          self.counts.insert(word.into_owned(), 1);
+        }
+      }
+    }
+  }
+
+  /// How many times a word has been seen so far.
+  pub fn get(&self, word: &str) -> usize {
+    self.counts.get(word).copied().unwrap_or(0)
+  }
+
+  /// Looks at the counts so far, in alphabetical order, without using up the
+  /// counter.
+  pub fn counts(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+    self.counts.iter().map(|(word, &count)| (word.as_str(), count))
+  }
+
+  /// Turns the counter into a map from each word to its count.
+  pub fn into_counts(self) -> BTreeMap<String, usize> {
+    self.counts
+  }
+}
+
+/// Splits the words out of some text.
+///
+/// This is Unicode word segmentation (UAX #29), keeping only the segments with
+/// at least one letter, number, mark, or connector punctuation in them.
+///
+/// Text that's entirely ASCII takes a faster byte based path, which gives back
+/// exactly the same words as the full segmentation would.
+///
+/// ```
+/// let words: Vec<&str> = whist::words("It's 3.14, e.g. x86_64: ok?").collect();
+/// assert_eq!(words, ["It's", "3.14", "e.g", "x86_64", "ok"]);
+/// ```
+///
+/// Text with no words in it, which includes empty text, text that's only
+/// whitespace, and text that's only symbols, gives back no words at all
+/// rather than any empty strings.
+///
+/// ```
+/// assert_eq!(whist::words("").count(), 0);
+/// assert_eq!(whist::words(" \t\n\u{3000}").count(), 0);
+/// assert_eq!(whist::words("-- !? ... ©").count(), 0);
+/// ```
+///
+/// Every Unicode space breaks words apart just like an ASCII space does, even
+/// the no-break ones common in copy-pasted text (`U+00A0`, `U+2007`, and
+/// `U+202F`). A no-break space only keeps a line from wrapping, it doesn't
+/// join two words into one. (UAX #29 on its own would keep words on both
+/// sides of a `U+202F` together, since Mongolian uses it within words, so
+/// those segments get split up again.)
+///
+/// ```
+/// let spaces = ["\u{A0}", "\u{2007}", "\u{2009}", "\u{200A}", "\u{202F}", "\u{205F}", "\u{3000}"];
+/// for space in spaces.iter() {
+///   let text = format!("one{space}two 3{space}000", space = space);
+///   let words: Vec<&str> = whist::words(&text).collect();
+///   assert_eq!(words, ["one", "two", "3", "000"]);
+/// }
+/// ```
+///
+/// A word never ends partway through a grapheme cluster. Combining marks, and
+/// the vowel signs and viramas of scripts like Devanagari, stay on the letter
+/// they're attached to. An emoji is a symbol rather than a word, and its skin
+/// tone modifier goes along with it instead of being left over as a word of
+/// its own.
+///
+/// ```
+/// let words: Vec<&str> = whist::words("नमस्ते, क्षत्रिय! cafe\u{301} hi👍🏽there 👍🏽").collect();
+/// assert_eq!(words, ["नमस्ते", "क्षत्रिय", "cafe\u{301}", "hi", "there"]);
+/// ```
+pub fn words(text: &str) -> Words<'_> {
+  if text.is_ascii() {
+    Words(WordsInner::Ascii(AsciiWords { text, pos: 0 }))
+  } else {
+    Words(WordsInner::Unicode(text.as_bytes().words(), None))
+  }
+}
+
+/// An iterator over the words of some text, made by [`words`].
+pub struct Words<'s>(WordsInner<'s>);
+
+enum WordsInner<'s> {
+  Ascii(AsciiWords<'s>),
+  /// The segments, and the pieces of the last one if it had a space in it.
+  Unicode(bstr::Words<'s>, Option<std::str::SplitWhitespace<'s>>),
+}
+
+impl<'s> Iterator for Words<'s> {
+  type Item = &'s str;
+
+  #[inline]
+  fn next(&mut self) -> Option<&'s str> {
+    match &mut self.0 {
+      WordsInner::Ascii(ascii) => ascii.next(),
+      WordsInner::Unicode(unicode, pieces) => loop {
+        if let Some(piece) = pieces.as_mut().and_then(Iterator::next) {
+          return Some(piece);
+        }
+        let word = unicode.next()?;
+        if !word.contains(char::is_whitespace) {
+          return Some(word);
+        }
+        *pieces = Some(word.split_whitespace());
+      },
+    }
+  }
+}
+
+/// Word segmentation for text that's known to be all ASCII.
+///
+/// Within ASCII, the UAX #29 rules boil down to this:
+/// * Letters, digits, and `_` always stay together.
+/// * `:`, `.`, or `'` between two letters stays within the word (`e.g`).
+/// * `,`, `;`, `.`, or `'` between two digits stays within the word (`1,000`).
+/// * Everything else breaks words apart, and isn't part of any word.
+struct AsciiWords<'s> {
+  text: &'s str,
+  pos: usize,
+}
+
+impl<'s> AsciiWords<'s> {
+  #[inline]
+  fn next(&mut self) -> Option<&'s str> {
+    let bytes = self.text.as_bytes();
+    let is_word_byte = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut pos = self.pos;
+    while pos < bytes.len() && !is_word_byte(bytes[pos]) {
+      pos += 1;
+    }
+    if pos == bytes.len() {
+      self.pos = pos;
+      return None;
+    }
+    let start = pos;
+    loop {
+      while pos < bytes.len() && is_word_byte(bytes[pos]) {
+        pos += 1;
+      }
+      if pos + 1 < bytes.len() {
+        let (left, mid, right) = (bytes[pos - 1], bytes[pos], bytes[pos + 1]);
+        let joined = (left.is_ascii_alphabetic()
+          && right.is_ascii_alphabetic()
+          && matches!(mid, b':' | b'.' | b'\''))
+          || (left.is_ascii_digit()
+            && right.is_ascii_digit()
+            && matches!(mid, b',' | b';' | b'.' | b'\''));
+        if joined {
+          pos += 2;
+          continue;
+        }
+      }
+      break;
+    }
+    self.pos = pos;
+    // Note(Lokathor): All the bytes are ASCII, so any index is a char boundary.
+    Some(&self.text[start..pos])
+  }
+}
+
+/// Splits the words out of some text like [`words`], but keeps a `#` or `@`
+/// that starts a word attached to it, for hashtags and mentions.
+///
+/// The sigil only counts when it's at the start of a token: right after a
+/// letter (as in an email address) it still splits the words apart.
+///
+/// ```
+/// let words: Vec<&str> = whist::social_words("#tag @user a@b, #1 ok# #").collect();
+/// assert_eq!(words, ["#tag", "@user", "a", "b", "#1", "ok"]);
+/// ```
+pub fn social_words(text: &str) -> SocialWords<'_> {
+  SocialWords { text, words: words(text) }
+}
+
+/// An iterator over the words of some text, made by [`social_words`].
+pub struct SocialWords<'s> {
+  text: &'s str,
+  words: Words<'s>,
+}
+
+impl<'s> Iterator for SocialWords<'s> {
+  type Item = &'s str;
+
+  fn next(&mut self) -> Option<&'s str> {
+    let word = self.words.next()?;
+    // Note(Lokathor): Every word is a slice of the text, so the pointers tell
+    // us where it is.
+    let start = word.as_ptr() as usize - self.text.as_ptr() as usize;
+    let before = &self.text.as_bytes()[..start];
+    match before {
+      [.., b'#' | b'@'] => {
+        let sigil_starts_token = match self.text[..start - 1].chars().next_back() {
+          Some(c) => !is_kinda_letter(c),
+          None => true,
+        };
+        if sigil_starts_token {
+          Some(&self.text[start - 1..start + word.len()])
+        } else {
+          Some(word)
+        }
+      }
+      _ => Some(word),
+    }
+  }
+}
+
+/// Splits some text into sentences.
+///
+/// A sentence ends at a `.`, `!`, or `?` (along with any closing quotes or
+/// brackets right after it) that's followed by whitespace and then something
+/// that isn't a lowercase letter, or by the end of the text. Each sentence is
+/// given back without the whitespace around it.
+///
+/// This is only a simple heuristic. An abbreviation followed by a capital
+/// letter (`Mr. Smith`) ends a sentence early, and a sentence that starts with
+/// a lowercase letter gets glued onto the one before it.
+///
+/// ```
+/// let text = "Hi there. How are you?! I'm fine, e.g. mostly. \"Ok.\" Done";
+/// let sentences: Vec<&str> = whist::sentences(text).collect();
+/// assert_eq!(sentences, ["Hi there.", "How are you?!", "I'm fine, e.g. mostly.", "\"Ok.\"", "Done"]);
+/// ```
+pub fn sentences(text: &str) -> Sentences<'_> {
+  Sentences { rest: text }
+}
+
+/// An iterator over the sentences of some text, made by [`sentences`].
+pub struct Sentences<'s> {
+  rest: &'s str,
+}
+
+impl<'s> Iterator for Sentences<'s> {
+  type Item = &'s str;
+
+  fn next(&mut self) -> Option<&'s str> {
+    let text = self.rest.trim_start();
+    if text.is_empty() {
+      self.rest = text;
+      return None;
+    }
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+      if !matches!(c, '.' | '!' | '?') {
+        continue;
+      }
+      while let Some((_, '.' | '!' | '?' | '"' | '\'' | ')' | ']' | '”' | '’')) = chars.peek() {
+        chars.next();
+      }
+      let end = chars.peek().map(|&(i, _)| i).unwrap_or(text.len());
+      let after = &text[end..];
+      let next_sentence = after.trim_start();
+      if after.is_empty()
+        || (next_sentence.len() < after.len()
+          && !next_sentence.chars().next().is_some_and(char::is_lowercase))
+      {
+        self.rest = next_sentence;
+        return Some(&text[..end]);
+      }
+    }
+    self.rest = "";
+    Some(text.trim_end())
+  }
+}
+
+/// Splits a word wherever a letter is next to a digit.
+///
+/// Chars that are neither letters nor digits (such as `_`) don't make a split
+/// of their own, they just stay with the fragment they're in.
+///
+/// ```
+/// use whist::split_alnum_transitions;
+/// assert_eq!(split_alnum_transitions("utf8").collect::<Vec<_>>(), ["utf", "8"]);
+/// assert_eq!(split_alnum_transitions("x86_64").collect::<Vec<_>>(), ["x", "86_64"]);
+/// assert_eq!(split_alnum_transitions("abc").collect::<Vec<_>>(), ["abc"]);
+/// assert_eq!(split_alnum_transitions("h2o").collect::<Vec<_>>(), ["h", "2", "o"]);
+/// ```
+pub fn split_alnum_transitions(word: &str) -> AlnumFragments<'_> {
+  AlnumFragments { rest: word }
+}
+
+/// An iterator over the parts of a word, made by [`split_alnum_transitions`].
+pub struct AlnumFragments<'s> {
+  rest: &'s str,
+}
+
+impl<'s> Iterator for AlnumFragments<'s> {
+  type Item = &'s str;
+
+  fn next(&mut self) -> Option<&'s str> {
+    if self.rest.is_empty() {
+      return None;
+    }
+    // the kind of the last letter or digit: `true` for a digit.
+    let mut last_was_digit: Option<bool> = None;
+    let mut end = self.rest.len();
+    for (i, c) in self.rest.char_indices() {
+      let is_digit = if c.is_alphabetic() {
+        false
+      } else if c.is_numeric() {
+        true
+      } else {
+        continue;
+      };
+      if last_was_digit.is_some_and(|last| last != is_digit) {
+        end = i;
+        break;
+      }
+      last_was_digit = Some(is_digit);
+    }
+    let (fragment, rest) = self.rest.split_at(end);
+    self.rest = rest;
+    Some(fragment)
+  }
+}
+
+/// Gives the shape of a word, with each uppercase letter as `X`, each other
+/// letter as `x`, and each digit as `d`.
+///
+/// Anything else, such as `'` or `_`, is kept as it is.
+///
+/// ```
+/// use whist::word_shape;
+/// assert_eq!(word_shape("Rust2024"), "Xxxxdddd");
+/// assert_eq!(word_shape("don't"), "xxx'x");
+/// assert_eq!(word_shape("ÉTÉ_1"), "XXX_d");
+/// ```
+pub fn word_shape(word: &str) -> String {
+  word
+    .chars()
+    .map(|c| {
+      if c.is_uppercase() {
+        'X'
+      } else if c.is_alphabetic() {
+        'x'
+      } else if c.is_numeric() {
+        'd'
+      } else {
+        c
+      }
+    })
+    .collect()
+}
+
+/// Replaces each run of digits in the word with a single `placeholder`.
+///
+/// ```
+/// use whist::mask_digits;
+/// assert_eq!(mask_digits("err404", '#'), "err#");
+/// assert_eq!(mask_digits("err503", '#'), "err#");
+/// assert_eq!(mask_digits("v1_2_30", '#'), "v#_#_#");
+/// assert_eq!(mask_digits("2024", 'N'), "N");
+/// assert_eq!(mask_digits("word", '#'), "word");
+/// ```
+pub fn mask_digits(word: &str, placeholder: char) -> Cow<'_, str> {
+  if !word.chars().any(char::is_numeric) {
+    return Cow::Borrowed(word);
+  }
+  let mut masked = String::with_capacity(word.len());
+  let mut in_digits = false;
+  for c in word.chars() {
+    if c.is_numeric() {
+      if !in_digits {
+        masked.push(placeholder);
+      }
+      in_digits = true;
+    } else {
+      masked.push(c);
+      in_digits = false;
+    }
+  }
+  Cow::Owned(masked)
+}
+
+/// Folds each char of the word that looks like some other char into the one
+/// it looks like, using the Unicode confusables data (the "skeleton" of UTS
+/// #39).
+///
+/// This is lossy on purpose: it's for counting look-alike spellings of a
+/// word, like one with a Cyrillic `а` in place of the Latin `a`, as the same
+/// word. Ordinary words can change too, such as `1` folding to `l`, so the
+/// output isn't always something that was in the text.
+///
+/// ```
+/// use whist::fold_confusables;
+/// // the first letter here is a Cyrillic `а`.
+/// assert_eq!(fold_confusables("\u{430}pple"), fold_confusables("apple"));
+/// // and this is Cyrillic `р`, `а`, `у`.
+/// assert_eq!(fold_confusables("\u{440}\u{430}\u{443}pal"), fold_confusables("paypal"));
+/// assert_ne!(fold_confusables("apple"), fold_confusables("apply"));
+/// ```
+pub fn fold_confusables(word: &str) -> String {
+  unicode_security::confusable_detection::skeleton(word).collect()
+}
+
+/// Takes the first of the `prefixes` that the word starts with off the front
+/// of the word.
+///
+/// A prefix that's the whole word isn't taken off, and with `case_sensitive`
+/// unset the prefixes match in any case.
+///
+/// ```
+/// use whist::strip_any_prefix;
+/// let prefixes = ["get_", "set_"];
+/// assert_eq!(strip_any_prefix("get_user", &prefixes, true), "user");
+/// assert_eq!(strip_any_prefix("set_user", &prefixes, true), "user");
+/// assert_eq!(strip_any_prefix("GET_user", &prefixes, true), "GET_user");
+/// assert_eq!(strip_any_prefix("GET_user", &prefixes, false), "user");
+/// assert_eq!(strip_any_prefix("get_", &prefixes, true), "get_");
+/// assert_eq!(strip_any_prefix("target", &prefixes, true), "target");
+/// ```
+pub fn strip_any_prefix<'w>(
+  word: &'w str, prefixes: &[impl AsRef<str>], case_sensitive: bool,
+) -> &'w str {
+  for prefix in prefixes {
+    let prefix = prefix.as_ref();
+    if prefix.len() >= word.len() {
+      continue;
+    }
+    let (head, rest) = match (word.get(..prefix.len()), word.get(prefix.len()..)) {
+      (Some(head), Some(rest)) => (head, rest),
+      _ => continue,
+    };
+    let matches =
+      if case_sensitive { head == prefix } else { head.to_lowercase() == prefix.to_lowercase() };
+    if matches {
+      return rest;
+    }
+  }
+  word
+}
+
+/// If a char can be part of what makes a segment of text count as a word.
+///
+/// These are the chars that regex calls `\w`: anything alphabetic, any
+/// number, any mark, any connector punctuation (such as `_`), and the two
+/// join controls. A segment from the word segmentation rules is only given
+/// back by [`words`] if it has at least one of these in it.
+///
+/// ```
+/// use whist::is_kinda_letter;
+/// assert!(is_kinda_letter('a'));
+/// assert!(is_kinda_letter('7'));
+/// assert!(is_kinda_letter('_'));
+/// assert!(is_kinda_letter('é'));
+/// assert!(is_kinda_letter('字'));
+/// assert!(!is_kinda_letter('\''));
+/// assert!(!is_kinda_letter(' '));
+/// assert!(!is_kinda_letter('€'));
+/// ```
+pub fn is_kinda_letter(c: char) -> bool {
+  if c.is_ascii() {
+    c.is_ascii_alphanumeric() || c == '_'
+  } else {
+    // Note(Lokathor): This asks the same check that `words` uses, so that the
+    // two can't ever disagree.
+    let mut buf = [0; 4];
+    c.encode_utf8(&mut buf).as_bytes().words().next().is_some()
+  }
+}
+
+/// The broad kinds of char, as far as splitting words goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharClass {
+  /// A char that [`is_kinda_letter`].
+  Letter,
+  /// A char with the Unicode `White_Space` property, see
+  /// [`char::is_whitespace`].
+  Whitespace,
+  /// Everything else: punctuation other than connectors, symbols, control
+  /// chars, and so on.
+  Symbol,
+}
+
+/// Sorts a char into its [`CharClass`].
+///
+/// ```
+/// use whist::{classify_char, CharClass};
+/// assert_eq!(classify_char('x'), CharClass::Letter);
+/// assert_eq!(classify_char('\u{3000}'), CharClass::Whitespace);
+/// assert_eq!(classify_char('\u{A0}'), CharClass::Whitespace);
+/// assert_eq!(classify_char('\u{202F}'), CharClass::Whitespace);
+/// assert_eq!(classify_char('-'), CharClass::Symbol);
+/// ```
+pub fn classify_char(c: char) -> CharClass {
+  if is_kinda_letter(c) {
+    CharClass::Letter
+  } else if c.is_whitespace() {
+    CharClass::Whitespace
+  } else {
+    CharClass::Symbol
+  }
+}
+
+/// The comment syntax of a source file, for use with [`strip_comments`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentStyle {
+  /// `//` line comments and `/* */` block comments, like C or Rust.
+  Slashes,
+  /// `#` line comments, like Python or shell scripts.
+  Hash,
+}
+impl CommentStyle {
+  /// Guesses the comment style from a file extension (without the dot).
+  ///
+  /// Gives `None` for extensions that aren't a known programming language.
+  pub fn from_extension(ext: &str) -> Option<Self> {
+    match ext.to_ascii_lowercase().as_str() {
+      "rs" | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "js" | "jsx" | "ts"
+      | "tsx" | "go" | "cs" | "swift" | "kt" | "scala" | "zig" | "css" | "scss" | "glsl"
+      | "wgsl" => Some(CommentStyle::Slashes),
+      "py" | "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "r" | "toml" | "yaml" | "yml"
+      | "cmake" | "mk" | "nim" | "ps1" => Some(CommentStyle::Hash),
+      _ => None,
+    }
+  }
+}
+
+/// Removes the comments from some source code.
+///
+/// Each comment is replaced with a single space (line comments keep their
+/// newline), so the words on either side of a comment don't get glued together.
+///
+/// This is just a heuristic: it knows nothing about string literals, so a
+/// comment marker inside of a string will still start a comment.
+///
+/// ```
+/// use whist::{strip_comments, CommentStyle};
+/// let code = "let x = 5; // five\n/* a\nblock */let y = 6;";
+/// assert_eq!(strip_comments(code, CommentStyle::Slashes), "let x = 5;  \n let y = 6;");
+/// let script = "echo hi # greet\necho bye";
+/// assert_eq!(strip_comments(script, CommentStyle::Hash), "echo hi  \necho bye");
+/// ```
+pub fn strip_comments(text: &str, style: CommentStyle) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  loop {
+    let start = match style {
+      CommentStyle::Slashes => {
+        let bytes = rest.as_bytes();
+        match rest
+          .match_indices('/')
+          .map(|(i, _)| i)
+          .find(|&i| matches!(bytes.get(i + 1), Some(b'/') | Some(b'*')))
+        {
+          Some(i) => i,
+          None => break,
+        }
+      }
+      CommentStyle::Hash => match rest.find('#') {
+        Some(i) => i,
+        None => break,
+      },
+    };
+    out.push_str(&rest[..start]);
+    out.push(' ');
+    let comment = &rest[start..];
+    rest = if let Some(block) = comment.strip_prefix("/*") {
+      match block.find("*/") {
+        Some(i) => &block[i + 2..],
+        None => "",
+      }
+    } else {
+      match comment.find('\n') {
+        Some(i) => &comment[i..],
+        None => "",
+      }
+    };
+  }
+  out.push_str(rest);
+  out
+}
+
+/// Removes the tags from some HTML, leaving just the text.
+///
+/// Each tag or `<!-- -->` comment is replaced with a single space, the
+/// contents of `<script>` and `<style>` elements are dropped entirely, and
+/// entities such as `&amp;`, `&#39;`, and `&#x1F600;` are decoded.
+///
+/// This isn't a full HTML parser. A tag in the middle of a word, as in
+/// `re<b>use</b>d`, splits the word. A `>` inside of an attribute value ends
+/// the tag early, and unknown named entities are left as is.
+///
+/// ```
+/// use whist::strip_html;
+/// let html = "<p class=\"x\">Fish &amp; chips</p><script>var a = 1;</script><!-- hi -->ok";
+/// assert_eq!(strip_html(html), " Fish & chips    ok");
+/// assert_eq!(strip_html("5 &lt; 6 &#x263A; &nope;"), "5 < 6 ☺ &nope;");
+/// assert_eq!(strip_html("<STYLE>p { }</Style>é"), "  é");
+/// ```
+pub fn strip_html(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find('<') {
+    decode_entities(&rest[..start], &mut out);
+    out.push(' ');
+    let tag = &rest[start..];
+    if let Some(comment) = tag.strip_prefix("<!--") {
+      rest = match comment.find("-->") {
+        Some(i) => &comment[i + 3..],
+        None => "",
+      };
+      continue;
+    }
+    let end = match tag.find('>') {
+      Some(i) => i + 1,
+      None => tag.len(),
+    };
+    let name: String = tag[1..]
+      .chars()
+      .take_while(char::is_ascii_alphanumeric)
+      .map(|c| c.to_ascii_lowercase())
+      .collect();
+    rest = &tag[end..];
+    if name == "script" || name == "style" {
+      let closing = format!("</{name}", name = name);
+      rest = match find_ignore_ascii_case(rest, &closing) {
+        Some(i) => &rest[i..],
+        None => "",
+      };
+    }
+  }
+  decode_entities(rest, &mut out);
+  out
+}
+
+/// The byte index of the first place `needle` shows up in `haystack`,
+/// ignoring ASCII case.
+///
+/// The `needle` has to start with an ASCII char, so that a match is always at
+/// a char boundary of `haystack`.
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+  haystack
+    .as_bytes()
+    .windows(needle.len())
+    .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Pushes the text onto `out` with any HTML entities in it decoded.
+fn decode_entities(mut text: &str, out: &mut String) {
+  while let Some(start) = text.find('&') {
+    out.push_str(&text[..start]);
+    let entity = &text[start..];
+    let decoded = entity.find(';').filter(|&end| end <= 10).and_then(|end| {
+      let c = match &entity[1..end] {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{A0}'),
+        name => match name.strip_prefix('#') {
+          Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok(),
+          Some(decimal) => decimal.parse().ok(),
+          None => None,
+        }
+        .and_then(char::from_u32),
+      };
+      c.map(|c| (c, end + 1))
+    });
+    match decoded {
+      Some((c, len)) => {
+        out.push(c);
+        text = &entity[len..];
+      }
+      None => {
+        out.push('&');
+        text = &entity[1..];
+      }
+    }
+  }
+  out.push_str(text);
+}
+
+/// Removes the code from some Markdown, leaving just the prose.
+///
+/// Fenced code blocks (between lines starting with ```` ``` ```` or `~~~`)
+/// become blank lines, and inline code spans become a single space.
+///
+/// This is a simple span stripper, not a full Markdown parser: indented code
+/// blocks are kept, and an inline code span has to start and end on the same
+/// line.
+///
+/// ```
+/// use whist::strip_markdown_code;
+/// let md = "Call `foo()` now.\n```rust\nlet x = 1;\n```\nDone, ``a`b``.\n";
+/// assert_eq!(strip_markdown_code(md), "Call   now.\n\n\n\nDone,  .\n");
+/// assert_eq!(strip_markdown_code("a ` b"), "a ` b");
+/// ```
+pub fn strip_markdown_code(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  // the char and length of the fence that opened the current code block.
+  let mut fence: Option<(char, usize)> = None;
+  for line in text.split_inclusive('\n') {
+    let trimmed = line.trim_start();
+    let run_of = |c: char| trimmed.chars().take_while(|&t| t == c).count();
+    match fence {
+      Some((c, len)) => {
+        if run_of(c) >= len {
+          fence = None;
+        }
+      }
+      None => {
+        let c = if trimmed.starts_with('~') { '~' } else { '`' };
+        let len = run_of(c);
+        if len >= 3 {
+          fence = Some((c, len));
+        } else {
+          strip_inline_code(line, &mut out);
+          continue;
+        }
+      }
+    }
+    if line.ends_with('\n') {
+      out.push('\n');
+    }
+  }
+  out
+}
+
+/// Pushes the line onto `out` with each inline code span swapped for a space.
+///
+/// A span starts with a run of backticks and ends at the next run of exactly
+/// as many backticks. A run that's never closed is just text.
+fn strip_inline_code(line: &str, out: &mut String) {
+  let mut rest = line;
+  while let Some(start) = rest.find('`') {
+    out.push_str(&rest[..start]);
+    let ticks = rest[start..].len() - rest[start..].trim_start_matches('`').len();
+    let after = &rest[start + ticks..];
+    let mut search = 0;
+    let close = loop {
+      match after[search..].find('`') {
+        None => break None,
+        Some(i) => {
+          let at = search + i;
+          let run = after[at..].len() - after[at..].trim_start_matches('`').len();
+          if run == ticks {
+            break Some(at + run);
+          }
+          search = at + run;
+        }
+      }
+    };
+    match close {
+      Some(end) => {
+        out.push(' ');
+        rest = &after[end..];
+      }
+      None => {
+        out.push_str(&rest[start..start + ticks]);
+        rest = after;
+      }
+    }
+  }
+  out.push_str(rest);
+}
+
+/// Swaps typographic punctuation for the plain ASCII version.
+///
+/// Curly quotes become straight quotes, dashes become `-`, and an ellipsis
+/// becomes `...`. That way `don’t` and `don't` end up as the same word. If
+/// there's nothing to swap the text is given back as is.
+///
+/// ```
+/// use whist::normalize_punctuation;
+/// assert_eq!(normalize_punctuation("“Don’t” — ok…"), "\"Don't\" - ok...");
+/// assert_eq!(normalize_punctuation("plain"), "plain");
+/// ```
+pub fn normalize_punctuation(text: &str) -> Cow<'_, str> {
+  fn ascii_for(c: char) -> Option<&'static str> {
+    Some(match c {
+      '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => "'",
+      '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' | '\u{00AB}' | '\u{00BB}' => {
+        "\""
+      }
+      '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}' | '\u{2212}' => {
+        "-"
+      }
+      '\u{2026}' => "...",
+      _ => return None,
+    })
+  }
+  if !text.chars().any(|c| ascii_for(c).is_some()) {
+    return Cow::Borrowed(text);
+  }
+  let mut out = String::with_capacity(text.len());
+  for c in text.chars() {
+    match ascii_for(c) {
+      Some(ascii) => out.push_str(ascii),
+      None => out.push(c),
+    }
+  }
+  Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The words of all-ASCII text, from the full Unicode segmentation instead
+  /// of the fast path.
+  fn unicode_words(text: &str) -> Vec<&str> {
+    Words(WordsInner::Unicode(text.as_bytes().words(), None)).collect()
+  }
+
+  fn assert_same_words(text: &str) {
+    assert!(text.is_ascii());
+    let fast: Vec<&str> = words(text).collect();
+    assert_eq!(fast, unicode_words(text), "splitting {text:?}", text = text);
+  }
+
+  #[test]
+  fn ascii_fast_path_matches_unicode_segmentation() {
+    // every short string made of the bytes that matter to the rules.
+    let alphabet = b"aZ7_:.',; \n\r-@";
+    let mut texts = vec![String::new()];
+    for _ in 0..4 {
+      texts = texts
+        .iter()
+        .flat_map(|text| {
+          alphabet.iter().map(move |&b| format!("{text}{b}", text = text, b = b as char))
+        })
+        .collect();
+      texts.iter().for_each(|text| assert_same_words(text));
+    }
+    // every ASCII byte, once and twice, between letters and digits.
+    for b in 0..=127u8 {
+      let c = b as char;
+      for &(left, right) in &[("a", "b"), ("1", "2"), ("a", "1"), ("1", "a"), ("_", "_")] {
+        assert_same_words(&format!("{l}{c}{r}", l = left, c = c, r = right));
+        assert_same_words(&format!("{l}{c}{c}{r}", l = left, c = c, r = right));
+      }
+    }
+    assert_same_words(
+      "It's 3.14, e.g. x86_64: ok? The U.S.A. won 1,000,000.5 ($12;34) at 9:30 \
+      'quoted' end. a.b.c 1.2.3 a..b 1,,2 http://example.com/a_b?c=d#e",
+    );
+  }
+
+  /// Times the fast path against the Unicode segmentation on 16 MB of
+  /// English-like text. Run it with
+  /// `cargo test --release --lib -- --ignored --nocapture ascii_fast_path_speed`.
+  #[test]
+  #[ignore]
+  fn ascii_fast_path_speed() {
+    use std::time::Instant;
+    const SAMPLE: &str = "The quick brown fox, who'd jumped over 3 lazy dogs, said: \"it's 9:30 \
+      already!\" Then it ran 1,000.5 miles (e.g. to the U.S.A.) and didn't stop.\n";
+    let text = SAMPLE.repeat(16 * 1024 * 1024 / SAMPLE.len());
+    let start = Instant::now();
+    let fast = words(&text).count();
+    let fast_time = start.elapsed();
+    let start = Instant::now();
+    let unicode = unicode_words(&text).len();
+    let unicode_time = start.elapsed();
+    assert_eq!(fast, unicode);
+    println!(
+      "{words} words: fast path {fast_time:?}, unicode {unicode_time:?}",
+      words = fast,
+      fast_time = fast_time,
+      unicode_time = unicode_time
+    );
+  }
+}
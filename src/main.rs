@@ -1,98 +1,3897 @@
 use std::{
-  collections::{BTreeMap, HashSet, VecDeque},
+  borrow::Cow,
+  cmp::{Ordering, Reverse},
+  collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque},
+  hash::Hash,
+  io::{BufWriter, Read, Seek, SeekFrom, Write},
+  ops::ControlFlow,
   path::{Path, PathBuf},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use bstr::ByteSlice;
 
+use encoding_rs::{Encoder, EncoderResult, Encoding};
+
+use deunicode::deunicode;
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+
+#[cfg(feature = "collate")]
+use icu_collator::{options::CollatorOptions, CollatorBorrowed, CollatorPreferences};
+
+#[cfg(feature = "collate")]
+use icu_locale_core::Locale;
+
+use icu_properties::{
+  props::{EmojiPresentation, GeneralCategory},
+  CodePointMapData, CodePointSetData, PropertyNamesLong,
+};
+
+#[cfg(feature = "watch")]
+use notify::{RecursiveMode, Watcher};
+
+use regex::Regex;
+
+use rustc_hash::FxHashSet;
+
+use sha2::{Digest, Sha256};
+
 use unicase::UniCase;
 
-#[rustfmt::skip]
-fn print_help() {
-  println!("whist is a word-histogram sort of utility.");
-  println!("--print-by-frequency     Will print the words by frequency.");
-  println!("--case-sensitive         Will make searches case sensitive.");
+use unicode_script::{Script, UnicodeScript};
+
+use whist::{
+  binary, fold_confusables, mask_digits, normalize_punctuation, sentences, social_words,
+  split_alnum_transitions, strip_any_prefix, strip_comments, strip_html, strip_markdown_code,
+  word_shape, words, CommentStyle,
+};
+
+mod repl;
+#[cfg(feature = "tui")]
+mod tui;
+mod wordcloud;
+
+#[rustfmt::skip]
+fn print_help() {
+  println!("whist is a word-histogram sort of utility.");
+  println!("It counts the files under the current directory, or under the directory in the");
+  println!("WHIST_ROOT environment variable if that's set. There's no path argument to override it.");
+  println!("Flags for the optional features (`clipboard`, `collate`, `sqlite`, `tui`, and `watch`)");
+  println!("are only listed if whist was built with them.");
+  println!("--print-by-frequency     Will print the words by frequency.");
+  println!("--case-sensitive         Will make searches case sensitive.");
+  println!("--with-doc-count         Also prints how many documents (files, by default) each word");
+  println!("                         was found in.");
+  println!("--exclude-extension EXT  Skips files with this extension. Can be used more than once.");
+  println!("--flush-every N          Flushes the printed words after every N of them, instead of");
+  println!("                         only when the output buffer fills up, so a pipe sees them");
+  println!("                         sooner.");
+  println!("--print0                 Ends each output record with a NUL byte instead of a newline.");
+  println!("--sort-by-length [DIR]   Prints the words by length, then frequency. DIR is");
+  println!("                         `longest` (the default) or `shortest` first.");
+  println!("--separators SEPS        Splits words only at the chars in SEPS, so everything else");
+  println!("                         (including whitespace not in SEPS) is part of a word.");
+  println!("--ignore-comments        Skips `//`, `/* */` and `#` comments in source files, picked");
+  println!("                         by extension. Experimental: comment markers inside of");
+  println!("                         strings will still start a comment.");
+  println!("--dictionary FILE        Only counts the words listed in FILE, one per line.");
+  println!("--print-zero             With --dictionary, also prints the words in FILE that were");
+  println!("                         never found, with a count of 0.");
+  println!("--buckets                Prints how many words have a count in each bucket, instead");
+  println!("                         of the words themselves.");
+  println!("--bucket-edges LIST      Comma separated top ends of each bucket (default 1,5,20,100).");
+  println!("--log-buckets            Prints how many words have a count in each power of two range");
+  println!("                         (1, 2-3, 4-7, and so on up to the biggest count), instead of");
+  println!("                         the words themselves. Good for log-log plots.");
+  println!("--vocab-growth           Prints how many new unique words each file added, instead");
+  println!("                         of the words. With --print-by-frequency the biggest");
+  println!("                         growth is printed first.");
+  println!("--head-bytes N           Only reads the first N bytes of each file. The last word");
+  println!("                         read from a file might be cut off part way.");
+  println!("--offset N               Skips the first N bytes of each file, such as to get past");
+  println!("                         license headers. With --length (or --head-bytes), only");
+  println!("                         that many bytes after the offset are read, for sampling the");
+  println!("                         same part of each file. Chars cut in half at either end of");
+  println!("                         the window are dropped, but a word might still be cut off");
+  println!("                         part way at either end and be miscounted.");
+  println!("--length N               The same as --head-bytes.");
+  println!("--by-mtime-year          Prints the total words from the files last modified in");
+  println!("                         each year, instead of the words.");
+  println!("--report-invalid-bytes   Prints how many bytes of each file weren't valid UTF-8.");
+  println!("                         Those bytes are always skipped, and the rest of the");
+  println!("                         file is still counted.");
+  println!("--repeat-threshold PCT   Warns about each file where a single word is more than");
+  println!("                         PCT percent of that file's words.");
+  #[cfg(feature = "tui")]
+  {
+    println!("--tui                    Opens the words in a scrollable view you can type into to");
+    println!("                         filter. Only works when the output is a terminal.");
+  }
+  println!("--interactive            After counting, reads commands to show just some of the");
+  println!("                         words, so they can be looked at different ways without");
+  println!("                         counting again: `top N`, `prefix P`, `min N`, `sort freq`,");
+  println!("                         `sort alpha`, `reset`, `help`, and `quit`. All the filters");
+  println!("                         given so far apply at once.");
+  println!("--also-json PATH         Also writes all the counts to PATH as a JSON object.");
+  println!("--report-json PATH       Also writes the counts to PATH as a JSON report that says");
+  println!("                         what run it came from, shaped like this:");
+  println!("                         {{\"meta\": {{\"version\": \"0.0.3\",");
+  println!("                                   \"timestamp\": \"2024-05-01T12:00:00Z\",");
+  println!("                                   \"files\": 12, \"tokens\": 3456,");
+  println!("                                   \"unique_words\": 789}},");
+  println!("                          \"counts\": {{\"the\": 123, ...}}}}");
+  println!("                         `version` is the whist version, `timestamp` is when the");
+  println!("                         report was written (UTC), `files` is how many files were");
+  println!("                         read, and `tokens` and `unique_words` are the total count");
+  println!("                         and how many different words there were. `counts` is the");
+  println!("                         same as --also-json writes.");
+  println!("--reverse-index PATH     Also writes which files each word was in, and how many");
+  println!("                         times, to PATH as a JSON object. This keeps an entry for");
+  println!("                         every word in every file it's in, so it can take a lot more");
+  println!("                         memory than the counts alone.");
+  println!("--weights FILE           Multiplies each file's words by the weight of the first");
+  println!("                         glob pattern it matches. Each line of FILE is a pattern");
+  println!("                         and then a weight, and unmatched files weigh 1. The");
+  println!("                         printed counts become (fractional) weighted sums, other");
+  println!("                         reports and saved counts stay whole numbers.");
+  println!("--time-decay HALFLIFE    Multiplies each file's words by 0.5^(age / HALFLIFE), where");
+  println!("                         age is how long ago the file was modified, so newer files");
+  println!("                         count more. HALFLIFE is like `30d`. The counts become");
+  println!("                         weighted sums like with --weights, and when both are given");
+  println!("                         a file's two weights are multiplied together.");
+  println!("--line-filter REGEX      Only counts the words on lines that match REGEX. Lines over");
+  println!("                         64 KiB are cut into pieces that are checked one by one.");
+  println!("--explain TEXT           Shows how TEXT gets split into words with the other flags");
+  println!("                         given, and what happens to each word, without counting any");
+  println!("                         files. Flags that go by the kind of file (like");
+  println!("                         --ignore-comments) don't apply.");
+  println!("--order-by REF.json      Prints the words in the frequency order of the counts in");
+  println!("                         REF.json (0 for words not found here), then the words");
+  println!("                         that REF.json doesn't have.");
+  println!("--max-files N            Stops after counting N files.");
+  println!("--timeout SECONDS        Stops starting new files after SECONDS have passed.");
+  println!("--normalize-punctuation  Turns curly quotes, dashes, and ellipses into ASCII before");
+  println!("                         counting, so `don’t` and `don't` are the same word.");
+  println!("--verbose                Also reports files that vanished before they could be read.");
+  println!("--transliterate          Romanizes each word into ASCII before counting it. This");
+  println!("                         is lossy and doesn't know the text's language. Greek,");
+  println!("                         Cyrillic, and accented Latin come out readable, other");
+  println!("                         scripts (CJK, Arabic, Indic, ...) only roughly.");
+  println!("--trailing-whitespace    Prints how many lines of each file end in spaces or tabs,");
+  println!("                         and the total, instead of the words.");
+  println!("--stats-per-file         Prints the bytes, chars, and words of each file, instead");
+  println!("                         of the words themselves.");
+  println!("--since DURATION         Only counts files modified within DURATION of now, such as");
+  println!("                         `90s`, `30m`, `24h`, `7d`, or `2w`.");
+  println!("--since-date DATE        Only counts files modified on or after DATE (YYYY-MM-DD, UTC).");
+  println!("--skip-unknown-mtime     With --since, skips files that have no readable modified time");
+  println!("                         instead of counting them with a warning.");
+  println!("--save-binary PATH       Also saves all the counts to PATH in whist's compact binary");
+  println!("                         format, sorted by word so that saves can be merged cheaply.");
+  println!("--load-binary PATH       Adds the counts saved in PATH to this run's counts. Can be");
+  println!("                         used more than once.");
+  println!("--update PATH            Adds this run's counts to the binary counts in PATH (if it");
+  println!("                         exists yet) and saves the total back to PATH.");
+  println!("--by-directory           Prints a separate list of words for each directory at the");
+  println!("                         top of the walk, with files right in the top counted as `.`.");
+  println!("--source LABEL=PATH      Counts the files under PATH instead of the root directory,");
+  println!("                         and prints a table of how many times each word was used");
+  println!("                         under each LABEL. Can be used more than once, and sources");
+  println!("                         with the same label are added together.");
+  println!("--by-language            Prints a separate list of words for each language, going by");
+  println!("                         a guess at the language of each whole file (as an ISO 639-3");
+  println!("                         code, like `eng`). The guess needs a few sentences of text");
+  println!("                         to be any good, mixes up close languages (such as Spanish");
+  println!("                         and Portuguese), and knows nothing about code, so short or");
+  println!("                         unclear files go under `unknown`. Can't be used with");
+  println!("                         --by-directory.");
+  println!("--approx-top N           Prints roughly the N most frequent words, always keeping");
+  println!("                         just 10*N counters in memory. The counts can come out low.");
+  println!("--approx-exact           With --approx-top, reads all the files a second time to get");
+  println!("                         exact counts of the words picked. The picks themselves are");
+  println!("                         still approximate.");
+  println!("--caps-ratio             Prints how many of the words are in all caps, instead of");
+  println!("                         the words. A word is all caps if it has an uppercase");
+  println!("                         letter and no lowercase ones, so `MAX_LEN` counts.");
+  println!("--heap-top N             Prints just the N most frequent words, most frequent first.");
+  println!("                         They're picked in one pass with a heap of N words instead");
+  println!("                         of sorting every word, which takes O(words * log N) time");
+  println!("                         and O(N) extra memory instead of O(words) for the sort.");
+  println!("                         Also cuts --also-json, --tui, --by-directory, and");
+  println!("                         --by-language down to N.");
+  println!("--positions              Prints how far through its files each word is on average,");
+  println!("                         from 0.000 (the very start) to 1.000 (the end), instead");
+  println!("                         of the counts.");
+  println!("--social                 Keeps a `#` or `@` at the start of a word as part of it, so");
+  println!("                         `#rustlang` and `@user` are words. A sigil in the middle of");
+  println!("                         a word (`a@b`) still splits it.");
+  println!("--min-total N            Exits with an error, without printing the words, if fewer");
+  println!("                         than N words were counted in total.");
+  println!("--intern-stats           Also reports how many words were interned, and how much");
+  println!("                         memory they take, once the counting is done.");
+  println!("--sentences              Prints the min, max, mean, and median words per sentence,");
+  println!("                         instead of the words. Sentences end at `.`, `!`, or `?`");
+  println!("                         and then a space, so abbreviations such as `Mr. Smith`");
+  println!("                         will cut some sentences in two.");
+  println!("--in-all-files           Only keeps the words that were in every document (file, by");
+  println!("                         default) read.");
+  println!("--document-unit UNIT     What --with-doc-count and --in-all-files count as a");
+  println!("                         document: `file` (the default), `directory` (each directory");
+  println!("                         at the top of the walk, like --by-directory), or `all`.");
+  println!("--redact                 Prints the first 8 hex digits of each word's SHA-256 hash");
+  println!("                         instead of the word, the same on every run. Also applies");
+  println!("                         to --also-json, --sqlite, and --tui, but not to");
+  println!("                         --save-binary.");
+  #[cfg(feature = "collate")]
+  {
+    println!("--collate LOCALE         Sorts the words alphabetically the way LOCALE (such as `fr`");
+    println!("                         or `sv-SE`) expects, so `é` is near `e` instead of after");
+    println!("                         `z`. Without it the sort is by the bytes of each word.");
+  }
+  println!("--around-symbol SYM      Prints the words that come right before SYM (such as `,`),");
+  println!("                         and then the words that come right after it, instead of");
+  println!("                         all the words.");
+  println!("--acronyms               Prints how many times each acronym was used, instead of the");
+  println!("                         words. An acronym is 2 to 5 letters that are all uppercase,");
+  println!("                         like `API` or `HTTP`.");
+  println!("--count-emoji            Prints how many times each emoji was used, instead of the");
+  println!("                         words. ZWJ sequences, flags, and skin tones each count as");
+  println!("                         one emoji. Which emoji are known comes from the Unicode");
+  println!("                         emoji data built in to the `emojis` crate (Unicode 17.0).");
+  println!("                         Chars like `©` only count when they're shown as emoji (with");
+  println!("                         a U+FE0F after them), and each emoji prints as written.");
+  println!("--ratio-to BASE.json     Prints how much more or less common each word is than in");
+  println!("                         BASE.json, as its share of all the words here over its");
+  println!("                         share there, biggest first. Words only here show as `new`");
+  println!("                         and words only in BASE.json show as `gone`.");
+  println!("--html                   Treats every file as HTML, only counting the text outside");
+  println!("                         of tags, `<script>`, and `<style>`, with entities such as");
+  println!("                         `&amp;` decoded. This is a simple tag stripper, not a full");
+  println!("                         HTML parser, so odd markup can confuse it.");
+  println!("--markdown-prose         Skips fenced code blocks and `inline code` in `.md` files.");
+  println!("                         This is a simple span stripper, not a full Markdown");
+  println!("                         parser, so indented code blocks are still counted.");
+  println!("--cap-per-file M         Only counts each word up to M times per file, so the counts");
+  println!("                         become sums of capped per-file counts and no one file can");
+  println!("                         dominate. Per-file reports still see every word.");
+  println!("--wordcloud PATH         Also draws the top 100 words (or --heap-top N words) as an");
+  println!("                         SVG word cloud at PATH, sized by how often they're used.");
+  println!("--dedupe-inodes          Only counts each file once, even if hard links or symlinks");
+  println!("                         to it are found too. Does nothing on platforms without");
+  println!("                         inodes, such as Windows.");
+  println!("--mem-report             Also reports how many bytes were read, and how big the");
+  println!("                         buffer that files are read into got.");
+  println!("--split-alnum-transitions");
+  println!("                         Splits words where a letter and a digit meet, so `utf8` is");
+  println!("                         counted as `utf` and `8`. Other chars, like the `_` in");
+  println!("                         `x86_64`, don't split, giving `x` and `86_64`.");
+  println!("--coverage PCT           Prints the most frequent words until they add up to PCT");
+  println!("                         percent of all the words, then how many words that took.");
+  println!("--read-retries N         Tries reading a file up to N more times if reading it fails,");
+  println!("                         waiting 50ms, then 100ms, and so on (up to 3.2s) between");
+  println!("                         tries. Good for flaky network drives.");
+  println!("--shapes                 Counts the shape of each word instead of the word, with");
+  println!("                         uppercase letters as `X`, other letters as `x`, and digits");
+  println!("                         as `d`, so `Rust2024` is `Xxxxdddd`. Always case sensitive.");
+  #[cfg(feature = "sqlite")]
+  {
+    println!("--sqlite PATH            Also writes all the counts to a `counts (word, count)` table");
+    println!("                         in the SQLite database at PATH, replacing what was there.");
+    println!("--sqlite-add             With --sqlite, adds the counts on to the ones already in the");
+    println!("                         table instead of replacing them.");
+  }
+  println!("--phrases FILE           Counts each phrase listed in FILE (one per line, such as");
+  println!("                         `New York`) as one word when its words come in a row. The");
+  println!("                         longest phrase that matches wins.");
+  println!("--words-only             Prints just the words, without their counts.");
+  println!("--flag-mixed-script      Prints just the words with letters from more than one");
+  println!("                         script, such as a Latin word with a Cyrillic `а` in it, and");
+  println!("                         the scripts involved. Some languages (Japanese) mix scripts");
+  println!("                         all the time, so not every hit is a look-alike.");
+  println!("--by-category            Prints how many words were mostly made of each Unicode");
+  println!("                         general category of char (`Lowercase_Letter`,");
+  println!("                         `Other_Letter` for CJK ideographs, `Decimal_Number`, ...),");
+  println!("                         instead of the words. Each word goes with the category most");
+  println!("                         of its chars are in, with ties going to the first one.");
+  println!("--drop-noise             Drops words that are both long and rare, such as base64 or");
+  println!("                         hex blobs. By default that's at least 20 chars long and");
+  println!("                         seen just once.");
+  println!("--noise-length N         With --drop-noise, the shortest word to drop (default 20).");
+  println!("--noise-count N          With --drop-noise, the biggest count to drop (default 1).");
+  println!("--sorted                 Reads the files in order of their paths, so per-file output");
+  println!("                         comes out the same on every run.");
+  #[cfg(feature = "clipboard")]
+  {
+    println!("--clipboard              Counts the words of the text on the clipboard instead of");
+    println!("                         any files.");
+  }
+  println!("--doubled-words          Prints each word that's the same as the word before it (such");
+  println!("                         as `the the`), with its file and how many words into the file");
+  println!("                         it is, instead of the words.");
+  println!("--min-alpha-ratio R      Drops words where less than R (from 0 to 1) of the chars are");
+  println!("                         letters, so `--min-alpha-ratio 0.6` drops `a1b2c3d4`.");
+  println!("--redundancy             Prints a rough measure of how repetitive the words are,");
+  println!("                         instead of the words: 1 - E / B, where B is the bytes read");
+  println!("                         and E is the sum over each distinct word w of");
+  println!("                         len(w) + count(w) * log2(total / count(w)) / 8, the bytes to");
+  println!("                         spell each word once and then entropy code every word seen.");
+  println!("                         Higher means more repetitive, and it can go below 0 for");
+  println!("                         very small inputs.");
+  println!("--external-merge         Keeps memory use down for huge inputs by saving the counts");
+  println!("                         to spill files in the temp directory whenever there are");
+  println!("                         too many different words, then merging the files at the");
+  println!("                         end. Only prints the alphabetical list of words (and like");
+  println!("                         --approx-top, in lowercase unless --case-sensitive), other");
+  println!("                         reports and outputs are skipped.");
+  println!("--memory-limit N         With --external-merge, how many different words to count");
+  println!("                         in memory before spilling them (default 1000000).");
+  println!("--strip-prefix P         Takes P off the front of each word that starts with it, as");
+  println!("                         long as something is left, so `get_user` counts as `user`.");
+  println!("                         Can be used more than once, and the first match is used.");
+  println!("--halt-on-error          Exits with an error as soon as a file or directory can't be");
+  println!("                         opened or read, or a file has bytes that aren't valid UTF-8,");
+  println!("                         instead of skipping past the problem. Files that vanish");
+  println!("                         during the walk are still skipped unless --verbose is given.");
+  println!("--introduction-curve     Prints a type-token curve instead of the words: for each");
+  println!("                         word number N, how many different words the files had used");
+  println!("                         by their Nth word, on average over the files with at least");
+  println!("                         N words. Each line is `N: average`, ready for plotting.");
+  println!("--editorconfig           Only counts the files matched by a section of the root's");
+  println!("                         .editorconfig that sets a charset or indent_style. This is");
+  println!("                         just a guess at which files are text, based on which files");
+  println!("                         the project bothered to configure.");
+  println!("--shebang [NAME]         Only counts the files without an extension if they start");
+  println!("                         with a `#!` line, like scripts do. With NAME, the script's");
+  println!("                         program must also start with NAME (so `python` finds");
+  println!("                         `#!/usr/bin/env python3`). Reads the first 256 bytes of");
+  println!("                         each file without an extension to check, before counting it.");
+  println!("--mask-digits            Replaces each run of digits in a word with `#` before counting");
+  println!("                         it, so `err404` and `err503` both count as `err#`.");
+  println!("--digit-placeholder C    With --mask-digits, the char to use instead of `#`.");
+  println!("--fold-confusables       Folds chars that look alike into one, using the Unicode");
+  println!("                         confusables data, before counting. This is lossy: it's meant");
+  println!("                         for counting spoofed look-alike spellings (like a Cyrillic");
+  println!("                         `а` in `аpple`) together, and can group normal words too");
+  println!("                         (`modern` with `rnodern`). Each group prints as the first");
+  println!("                         spelling of it that was seen.");
+  println!("--checkpoint PATH        Saves which files have been counted and the counts so far to");
+  println!("                         PATH every so often. If PATH is there when whist starts then");
+  println!("                         the counts are loaded back and those files are skipped, so");
+  println!("                         an interrupted run can pick up where it left off. PATH is");
+  println!("                         deleted when a run finishes. Only the word counts carry");
+  println!("                         over, other reports only cover the files counted after the");
+  println!("                         resume.");
+  println!("--checkpoint-every N     With --checkpoint, saves after every N files (default 100).");
+  println!("--preset NAME            Turns on a bundle of flags for a common kind of input. Flags");
+  println!("                         given as well still apply, and their values win out.");
+  println!("                         `prose`: --normalize-punctuation --markdown-prose");
+  println!("                         `code`: --ignore-comments --case-sensitive");
+  println!("                                 --min-alpha-ratio 0.5");
+  println!("                         `logs`: --mask-digits --drop-noise");
+  println!("--prometheus             Prints the counts as Prometheus text format metrics, one");
+  println!("                         `whist_word_count{{word=\"the\"}} 1234` line per word, for a");
+  println!("                         scraper or push gateway to pick up. Every word is its own");
+  println!("                         series, so just the top 100 words are printed unless");
+  println!("                         --heap-top N says how many.");
+  println!("--extract-urls           Counts each whole URL (anything like `scheme://...`) as one");
+  println!("                         token before splitting up words, and takes it out of the");
+  println!("                         text so its pieces don't count as words. The URL counts are");
+  println!("                         printed after the words, under `urls:`. A URL ends at a");
+  println!("                         space, quote, or `<>`, and trailing punctuation (along with a");
+  println!("                         `)` that doesn't close a `(` in the URL) isn't part of it.");
+  println!("                         URLs without a scheme, like `example.com`, aren't found.");
+  println!("--extract-paths          With --extract-urls, also counts whole file paths, printed");
+  println!("                         under `paths:`. A path starts with `/`, `~/`, `./`, `../`,");
+  println!("                         or a drive like `C:\\`, or is a relative path with a `/` whose");
+  println!("                         last part has an extension, like `src/main.rs`.");
+  println!("--output-encoding NAME   Writes the list of words and counts in the encoding NAME");
+  println!("                         (such as `windows-1252`, `shift_jis`, or `koi8-r`) instead of");
+  println!("                         UTF-8, for consoles and tools that expect a code page. Any");
+  println!("                         WHATWG encoding label works, except for UTF-16. Other");
+  println!("                         reports are still written as UTF-8.");
+  println!("--unmappable MODE        With --output-encoding, what to do with a char the encoding");
+  println!("                         doesn't have: `replace` it with `?` (the default), or");
+  println!("                         `error` out.");
+  #[cfg(feature = "watch")]
+  println!("--watch                  Keeps running, re-counting whenever a file changes.");
+}
+
+/// The settings picked on the command line.
+struct Config {
+  print_by_frequency: bool,
+  case_sensitive: bool,
+  with_doc_count: bool,
+  exclude_extensions: Vec<UniCase<String>>,
+  print0: bool,
+  sort_by_length: Option<LengthOrder>,
+  separators: Option<Vec<char>>,
+  ignore_comments: bool,
+  /// When counting case insensitively all the words in here are lowercase.
+  dictionary: Option<FxHashSet<String>>,
+  /// The top end of each `--buckets` bucket.
+  buckets: Option<Vec<usize>>,
+  vocab_growth: bool,
+  head_bytes: Option<u64>,
+  by_mtime_year: bool,
+  report_invalid_bytes: bool,
+  repeat_threshold: Option<f64>,
+  #[cfg(feature = "tui")]
+  tui: bool,
+  also_json: Option<String>,
+  report_json: Option<String>,
+  weights: Option<Weights>,
+  line_filter: Option<Regex>,
+  /// The reference counts, most frequent first.
+  order_by: Option<Vec<(String, usize)>>,
+  max_files: Option<usize>,
+  timeout: Option<Duration>,
+  normalize_punctuation: bool,
+  verbose: bool,
+  transliterate: bool,
+  stats_per_file: bool,
+  /// Files last modified before this are skipped.
+  since: Option<SystemTime>,
+  skip_unknown_mtime: bool,
+  save_binary: Option<String>,
+  load_binary: Vec<String>,
+  by_directory: bool,
+  approx_top: Option<usize>,
+  approx_exact: bool,
+  caps_ratio: bool,
+  update: Option<String>,
+  heap_top: Option<usize>,
+  positions: bool,
+  social: bool,
+  min_total: Option<usize>,
+  intern_stats: bool,
+  sentences: bool,
+  in_all_files: bool,
+  redact: bool,
+  /// Sorts the alphabetical output in the order a locale expects.
+  collator: Option<Collator>,
+  count_emoji: bool,
+  /// The baseline counts for `--ratio-to`, most frequent first.
+  ratio_to: Option<Vec<(String, usize)>>,
+  html: bool,
+  markdown_prose: bool,
+  cap_per_file: Option<usize>,
+  wordcloud: Option<String>,
+  dedupe_inodes: bool,
+  mem_report: bool,
+  split_alnum_transitions: bool,
+  coverage: Option<f64>,
+  read_retries: u32,
+  shapes: bool,
+  sqlite: Option<String>,
+  sqlite_add: bool,
+  phrases: Option<Phrases>,
+  words_only: bool,
+  flag_mixed_script: bool,
+  by_category: bool,
+  /// The shortest length and the biggest count of words to drop as noise.
+  drop_noise: Option<(usize, usize)>,
+  sorted: bool,
+  #[cfg(feature = "clipboard")]
+  clipboard: bool,
+  doubled_words: bool,
+  log_buckets: bool,
+  min_alpha_ratio: Option<f64>,
+  /// The directory to count the files of.
+  root: PathBuf,
+  time_decay: Option<Duration>,
+  print_zero: bool,
+  redundancy: bool,
+  flush_every: Option<usize>,
+  by_language: bool,
+  /// With `--external-merge`, how many different words to count in memory
+  /// before spilling them to disk.
+  external_merge: Option<usize>,
+  strip_prefixes: Vec<String>,
+  halt_on_error: bool,
+  acronyms: bool,
+  /// The `--source` directories to walk instead of the root, with their labels.
+  sources: Vec<(String, PathBuf)>,
+  introduction_curve: bool,
+  /// With `--editorconfig`, the files that count as text.
+  editorconfig: Option<GlobSet>,
+  trailing_whitespace: bool,
+  /// With `--shebang`, the start of the program name scripts must run, which
+  /// is empty to allow any program.
+  shebang: Option<String>,
+  /// With `--mask-digits`, the char that each run of digits becomes.
+  mask_digits: Option<char>,
+  /// With `--checkpoint`, the path to save to and how many files to count
+  /// between saves.
+  checkpoint: Option<(String, usize)>,
+  reverse_index: Option<String>,
+  around_symbol: Option<String>,
+  document_unit: DocumentUnit,
+  fold_confusables: bool,
+  prometheus: bool,
+  /// With `--extract-urls`, what a URL (or a path, with `--extract-paths`)
+  /// looks like.
+  extract_urls: Option<Regex>,
+  /// With `--offset`, how many bytes at the start of each file to skip.
+  offset: u64,
+  interactive: bool,
+  /// With `--output-encoding`, the encoding to write the counts in, and if an
+  /// unmappable char is an error.
+  output_encoding: Option<(&'static Encoding, bool)>,
+}
+
+/// Which words `--sort-by-length` puts first.
+#[derive(Clone, Copy)]
+enum LengthOrder {
+  Longest,
+  Shortest,
+}
+
+/// What `--with-doc-count` and `--in-all-files` count as one document.
+#[derive(Clone, Copy)]
+enum DocumentUnit {
+  File,
+  /// Each directory at the top of the walk, the same as `--by-directory`.
+  Directory,
+  /// Everything read, as a single document.
+  All,
+}
+
+/// How long the file system has to be quiet before `--watch` re-counts.
+#[cfg(feature = "watch")]
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(250);
+
+fn main() {
+  let mut args: Vec<String> = std::env::args().collect();
+  if args.iter().any(|s| s.as_str() == "--help") {
+    print_help();
+    return;
+  }
+  // Note(Lokathor): The preset's flags go in before all the other args, so a
+  // flag given for real comes after any preset value and wins out over it.
+  let presets: Vec<&'static [&'static str]> =
+    arg_values(&args, "--preset").into_iter().map(preset_args).collect();
+  for preset in presets.into_iter().rev() {
+    args.splice(1..1, preset.iter().map(|arg| arg.to_string()));
+  }
+  // the flags for the optional features, which are only there if whist was
+  // built with them.
+  let optional_flags = [
+    ("--clipboard", "clipboard", cfg!(feature = "clipboard")),
+    ("--collate", "collate", cfg!(feature = "collate")),
+    ("--sqlite", "sqlite", cfg!(feature = "sqlite")),
+    ("--sqlite-add", "sqlite", cfg!(feature = "sqlite")),
+    ("--tui", "tui", cfg!(feature = "tui")),
+    ("--watch", "watch", cfg!(feature = "watch")),
+  ];
+  for &(flag, feature, built) in optional_flags.iter() {
+    if !built && args.iter().any(|s| s.as_str() == flag) {
+      eprintln!(
+        "{flag} needs whist to be built with the `{feature}` feature.",
+        flag = flag,
+        feature = feature
+      );
+      std::process::exit(1);
+    }
+  }
+  let config = parse_config(&args, root_dir());
+  if [config.by_directory, config.by_language, !config.sources.is_empty()]
+    .iter()
+    .filter(|&&on| on)
+    .count()
+    > 1
+  {
+    eprintln!("Only one of --by-directory, --by-language, and --source can be given.");
+    std::process::exit(1);
+  }
+  if config.external_merge.is_some() && config.approx_top.is_some() {
+    eprintln!("Only one of --external-merge and --approx-top can be given.");
+    std::process::exit(1);
+  }
+  if config.checkpoint.is_some() && (config.external_merge.is_some() || config.approx_top.is_some())
+  {
+    eprintln!("--checkpoint can't be used with --external-merge or --approx-top.");
+    std::process::exit(1);
+  }
+  if config.heap_top.is_some() && config.order_by.is_some() {
+    eprintln!("--order-by can't be used with --heap-top (or --prometheus, which uses it).");
+    std::process::exit(1);
+  }
+  if (config.weights.is_some() || config.time_decay.is_some())
+    && (config.heap_top.is_some() || config.with_doc_count)
+  {
+    eprintln!(
+      "--weights and --time-decay can't be used with --heap-top, --prometheus, or \
+      --with-doc-count."
+    );
+    std::process::exit(1);
+  }
+  if config.extract_urls.is_some() && config.prometheus {
+    eprintln!("--extract-urls can't be used with --prometheus.");
+    std::process::exit(1);
+  }
+  // each re-count would add all the counts on to these files again.
+  if args.iter().any(|s| s.as_str() == "--watch") && (config.update.is_some() || config.sqlite_add)
+  {
+    eprintln!("--watch can't be used with --update or --sqlite-add.");
+    std::process::exit(1);
+  }
+  // Note(Lokathor): The intern set lives outside of `run` so that re-counting
+  // in watch mode only leaks the words we haven't seen before.
+  let mut intern: HashSet<&'static str> = HashSet::new();
+
+  if let Some(text) = arg_values(&args, "--explain").last() {
+    explain(&config, text);
+    return;
+  }
+  #[cfg(feature = "watch")]
+  if args.iter().any(|s| s.as_str() == "--watch") {
+    watch(&config, &mut intern);
+    return;
+  }
+  if run(&config, &mut intern).is_err() {
+    std::process::exit(1);
+  }
+}
+
+/// Makes the `Config` for the command line `args` (after any `--preset` has
+/// been put in), counting the files in `root`.
+fn parse_config(args: &[String], root: PathBuf) -> Config {
+  let case_sensitive = args.iter().any(|s| s.as_str() == "--case-sensitive");
+  Config {
+    print_by_frequency: args.iter().any(|s| s.as_str() == "--print-by-frequency"),
+    case_sensitive,
+    with_doc_count: args.iter().any(|s| s.as_str() == "--with-doc-count"),
+    exclude_extensions: arg_values(args, "--exclude-extension")
+      .into_iter()
+      .map(|ext| UniCase::new(ext.trim_start_matches('.').to_string()))
+      .collect(),
+    print0: args.iter().any(|s| s.as_str() == "--print0"),
+    sort_by_length: args.iter().position(|s| s.as_str() == "--sort-by-length").map(|i| match args
+      .get(i + 1)
+      .map(String::as_str)
+    {
+      Some("shortest") => LengthOrder::Shortest,
+      _ => LengthOrder::Longest,
+    }),
+    separators: arg_values(args, "--separators").last().map(|seps| seps.chars().collect()),
+    ignore_comments: args.iter().any(|s| s.as_str() == "--ignore-comments"),
+    dictionary: arg_values(args, "--dictionary")
+      .last()
+      .map(|path| load_word_list(path, case_sensitive)),
+    buckets: if args.iter().any(|s| s.as_str() == "--buckets") {
+      Some(match arg_values(args, "--bucket-edges").last() {
+        Some(list) => parse_bucket_edges(list),
+        None => vec![1, 5, 20, 100],
+      })
+    } else {
+      None
+    },
+    vocab_growth: args.iter().any(|s| s.as_str() == "--vocab-growth"),
+    by_mtime_year: args.iter().any(|s| s.as_str() == "--by-mtime-year"),
+    report_invalid_bytes: args.iter().any(|s| s.as_str() == "--report-invalid-bytes"),
+    #[cfg(feature = "tui")]
+    tui: args.iter().any(|s| s.as_str() == "--tui"),
+    also_json: arg_values(args, "--also-json").last().map(|path| path.to_string()),
+    report_json: arg_values(args, "--report-json").last().map(|path| path.to_string()),
+    weights: arg_values(args, "--weights").last().map(|path| Weights::load(path)),
+    line_filter: arg_values(args, "--line-filter")
+      .last()
+      .map(|pattern| parse_arg("--line-filter", pattern)),
+    since: match (arg_values(args, "--since").last(), arg_values(args, "--since-date").last()) {
+      (Some(_), Some(_)) => {
+        eprintln!("Only one of --since and --since-date can be given.");
+        std::process::exit(1);
+      }
+      (Some(duration), None) => {
+        match SystemTime::now().checked_sub(parse_duration("--since", duration)) {
+          Some(since) => Some(since),
+          None => {
+            eprintln!(
+              "Bad value for --since, `{duration}`: that's too long ago.",
+              duration = duration
+            );
+            std::process::exit(1);
+          }
+        }
+      }
+      (None, Some(date)) => Some(parse_date(date)),
+      (None, None) => None,
+    },
+    save_binary: arg_values(args, "--save-binary").last().map(|path| path.to_string()),
+    load_binary: arg_values(args, "--load-binary").into_iter().map(String::from).collect(),
+    by_directory: args.iter().any(|s| s.as_str() == "--by-directory"),
+    approx_top: arg_values(args, "--approx-top").last().map(|n| parse_arg("--approx-top", n)),
+    approx_exact: args.iter().any(|s| s.as_str() == "--approx-exact"),
+    caps_ratio: args.iter().any(|s| s.as_str() == "--caps-ratio"),
+    update: arg_values(args, "--update").last().map(|path| path.to_string()),
+    heap_top: match arg_values(args, "--heap-top").last() {
+      Some(n) => Some(parse_arg("--heap-top", n)),
+      None if args.iter().any(|s| s.as_str() == "--prometheus") => Some(PROMETHEUS_WORDS),
+      None => None,
+    },
+    positions: args.iter().any(|s| s.as_str() == "--positions"),
+    social: args.iter().any(|s| s.as_str() == "--social"),
+    min_total: arg_values(args, "--min-total").last().map(|n| parse_arg("--min-total", n)),
+    intern_stats: args.iter().any(|s| s.as_str() == "--intern-stats"),
+    sentences: args.iter().any(|s| s.as_str() == "--sentences"),
+    in_all_files: args.iter().any(|s| s.as_str() == "--in-all-files"),
+    redact: args.iter().any(|s| s.as_str() == "--redact"),
+    #[cfg(feature = "collate")]
+    collator: arg_values(args, "--collate").last().map(|locale| load_collator(locale)),
+    #[cfg(not(feature = "collate"))]
+    collator: None,
+    count_emoji: args.iter().any(|s| s.as_str() == "--count-emoji"),
+    ratio_to: arg_values(args, "--ratio-to").last().map(|path| load_json_counts(path)),
+    html: args.iter().any(|s| s.as_str() == "--html"),
+    markdown_prose: args.iter().any(|s| s.as_str() == "--markdown-prose"),
+    dedupe_inodes: args.iter().any(|s| s.as_str() == "--dedupe-inodes"),
+    mem_report: args.iter().any(|s| s.as_str() == "--mem-report"),
+    split_alnum_transitions: args.iter().any(|s| s.as_str() == "--split-alnum-transitions"),
+    shapes: args.iter().any(|s| s.as_str() == "--shapes"),
+    sqlite: arg_values(args, "--sqlite").last().map(|path| path.to_string()),
+    sqlite_add: args.iter().any(|s| s.as_str() == "--sqlite-add"),
+    words_only: args.iter().any(|s| s.as_str() == "--words-only"),
+    flag_mixed_script: args.iter().any(|s| s.as_str() == "--flag-mixed-script"),
+    by_category: args.iter().any(|s| s.as_str() == "--by-category"),
+    sorted: args.iter().any(|s| s.as_str() == "--sorted"),
+    #[cfg(feature = "clipboard")]
+    clipboard: args.iter().any(|s| s.as_str() == "--clipboard"),
+    doubled_words: args.iter().any(|s| s.as_str() == "--doubled-words"),
+    log_buckets: args.iter().any(|s| s.as_str() == "--log-buckets"),
+    min_alpha_ratio: arg_values(args, "--min-alpha-ratio")
+      .last()
+      .map(|r| parse_arg("--min-alpha-ratio", r)),
+    editorconfig: if args.iter().any(|s| s.as_str() == "--editorconfig") {
+      Some(load_editorconfig(&root))
+    } else {
+      None
+    },
+    root,
+    print_zero: args.iter().any(|s| s.as_str() == "--print-zero"),
+    redundancy: args.iter().any(|s| s.as_str() == "--redundancy"),
+    flush_every: arg_values(args, "--flush-every").last().map(|n| parse_arg("--flush-every", n)),
+    by_language: args.iter().any(|s| s.as_str() == "--by-language"),
+    halt_on_error: args.iter().any(|s| s.as_str() == "--halt-on-error"),
+    acronyms: args.iter().any(|s| s.as_str() == "--acronyms"),
+    shebang: args.iter().position(|s| s.as_str() == "--shebang").map(|i| {
+      match args.get(i + 1).filter(|name| !name.starts_with("--")) {
+        Some(name) => name.to_string(),
+        None => String::new(),
+      }
+    }),
+    mask_digits: if args.iter().any(|s| s.as_str() == "--mask-digits") {
+      Some(
+        arg_values(args, "--digit-placeholder")
+          .last()
+          .map(|c| parse_arg("--digit-placeholder", c))
+          .unwrap_or('#'),
+      )
+    } else {
+      None
+    },
+    checkpoint: arg_values(args, "--checkpoint").last().map(|path| {
+      let every = arg_values(args, "--checkpoint-every")
+        .last()
+        .map(|n| parse_arg("--checkpoint-every", n))
+        .unwrap_or(100);
+      (path.to_string(), every)
+    }),
+    reverse_index: arg_values(args, "--reverse-index").last().map(|path| path.to_string()),
+    around_symbol: arg_values(args, "--around-symbol").last().map(|sym| sym.to_string()),
+    document_unit: match arg_values(args, "--document-unit").last() {
+      None | Some(&"file") => DocumentUnit::File,
+      Some(&"directory") => DocumentUnit::Directory,
+      Some(&"all") => DocumentUnit::All,
+      Some(unit) => {
+        eprintln!(
+          "Bad value for --document-unit, `{unit}`: it must be file, directory, or all.",
+          unit = unit
+        );
+        std::process::exit(1);
+      }
+    },
+    extract_urls: if args.iter().any(|s| s.as_str() == "--extract-urls") {
+      if args.iter().any(|s| s.as_str() == "--extract-paths") {
+        Some(parse_arg(
+          "--extract-paths",
+          &format!("{url}|{path}", url = URL_PATTERN, path = PATH_PATTERN),
+        ))
+      } else {
+        Some(parse_arg("--extract-urls", URL_PATTERN))
+      }
+    } else {
+      None
+    },
+    output_encoding: arg_values(args, "--output-encoding").last().map(|name| {
+      let encoding = match Encoding::for_label(name.as_bytes()) {
+        // UTF-16 and `replacement` can only be decoded, not encoded.
+        Some(encoding) if encoding.output_encoding() == encoding => encoding,
+        Some(_) => {
+          eprintln!("--output-encoding can't write `{name}` text.", name = name);
+          std::process::exit(1);
+        }
+        None => {
+          eprintln!("Bad value for --output-encoding, `{name}`: unknown encoding.", name = name);
+          std::process::exit(1);
+        }
+      };
+      let error_on_unmappable = match arg_values(args, "--unmappable").last() {
+        None | Some(&"replace") => false,
+        Some(&"error") => true,
+        Some(mode) => {
+          eprintln!(
+            "Bad value for --unmappable, `{mode}`: it must be replace or error.",
+            mode = mode
+          );
+          std::process::exit(1);
+        }
+      };
+      (encoding, error_on_unmappable)
+    }),
+    offset: arg_values(args, "--offset").last().map(|n| parse_arg("--offset", n)).unwrap_or(0),
+    interactive: args.iter().any(|s| s.as_str() == "--interactive"),
+    prometheus: args.iter().any(|s| s.as_str() == "--prometheus"),
+    fold_confusables: args.iter().any(|s| s.as_str() == "--fold-confusables"),
+    trailing_whitespace: args.iter().any(|s| s.as_str() == "--trailing-whitespace"),
+    introduction_curve: args.iter().any(|s| s.as_str() == "--introduction-curve"),
+    sources: arg_values(args, "--source").into_iter().map(parse_source).collect(),
+    strip_prefixes: arg_values(args, "--strip-prefix").into_iter().map(String::from).collect(),
+    external_merge: if args.iter().any(|s| s.as_str() == "--external-merge") {
+      Some(
+        arg_values(args, "--memory-limit")
+          .last()
+          .map(|n| parse_arg("--memory-limit", n))
+          .unwrap_or(1_000_000),
+      )
+    } else {
+      None
+    },
+    time_decay: arg_values(args, "--time-decay").last().map(|half_life| {
+      let half_life = parse_duration("--time-decay", half_life);
+      if half_life.is_zero() {
+        eprintln!("Bad value for --time-decay: the half-life can't be zero.");
+        std::process::exit(1);
+      }
+      half_life
+    }),
+    drop_noise: if args.iter().any(|s| s.as_str() == "--drop-noise") {
+      Some((
+        arg_values(args, "--noise-length")
+          .last()
+          .map(|n| parse_arg("--noise-length", n))
+          .unwrap_or(20),
+        arg_values(args, "--noise-count")
+          .last()
+          .map(|n| parse_arg("--noise-count", n))
+          .unwrap_or(1),
+      ))
+    } else {
+      None
+    },
+    phrases: arg_values(args, "--phrases").last().map(|path| Phrases::load(path, case_sensitive)),
+    read_retries: arg_values(args, "--read-retries")
+      .last()
+      .map(|n| parse_arg("--read-retries", n))
+      .unwrap_or(0),
+    coverage: arg_values(args, "--coverage")
+      .last()
+      .map(|pct| parse_arg("--coverage", pct.trim_end_matches('%'))),
+    wordcloud: arg_values(args, "--wordcloud").last().map(|path| path.to_string()),
+    cap_per_file: arg_values(args, "--cap-per-file").last().map(|m| parse_arg("--cap-per-file", m)),
+    skip_unknown_mtime: args.iter().any(|s| s.as_str() == "--skip-unknown-mtime"),
+    stats_per_file: args.iter().any(|s| s.as_str() == "--stats-per-file"),
+    transliterate: args.iter().any(|s| s.as_str() == "--transliterate"),
+    verbose: args.iter().any(|s| s.as_str() == "--verbose"),
+    normalize_punctuation: args.iter().any(|s| s.as_str() == "--normalize-punctuation"),
+    max_files: arg_values(args, "--max-files").last().map(|n| parse_arg("--max-files", n)),
+    timeout: arg_values(args, "--timeout").last().map(|secs| {
+      match Duration::try_from_secs_f64(parse_arg("--timeout", secs)) {
+        Ok(timeout) => timeout,
+        Err(e) => {
+          eprintln!("Bad value for --timeout, `{secs}`: {e}", secs = secs, e = e);
+          std::process::exit(1);
+        }
+      }
+    }),
+    order_by: arg_values(args, "--order-by").last().map(|path| load_json_counts(path)),
+    repeat_threshold: arg_values(args, "--repeat-threshold")
+      .last()
+      .map(|pct| parse_arg("--repeat-threshold", pct.trim_end_matches('%'))),
+    head_bytes: match (arg_values(args, "--head-bytes").last(), arg_values(args, "--length").last())
+    {
+      (Some(_), Some(_)) => {
+        eprintln!("Only one of --head-bytes and --length can be given.");
+        std::process::exit(1);
+      }
+      (Some(n), None) => Some(parse_arg("--head-bytes", n)),
+      (None, Some(n)) => Some(parse_arg("--length", n)),
+      (None, None) => None,
+    },
+  }
+}
+
+/// How `--collate` compares words.
+#[cfg(feature = "collate")]
+type Collator = CollatorBorrowed<'static>;
+
+/// Without the `collate` feature there's no `--collate`, so there's never a
+/// `Collator` to compare with.
+#[cfg(not(feature = "collate"))]
+enum Collator {}
+
+#[cfg(not(feature = "collate"))]
+impl Collator {
+  fn compare(&self, _: &str, _: &str) -> Ordering {
+    match *self {}
+  }
+}
+
+/// Sets up the `--collate` sorting for a locale, such as `en`, `de`, or `sv-SE`.
+#[cfg(feature = "collate")]
+fn load_collator(locale: &str) -> CollatorBorrowed<'static> {
+  let locale: Locale = parse_arg("--collate", locale);
+  match CollatorBorrowed::try_new(CollatorPreferences::from(&locale), CollatorOptions::default()) {
+    Ok(collator) => collator,
+    Err(e) => {
+      eprintln!("Couldn't set up sorting for the `{locale}` locale: {e}", locale = locale, e = e);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// The flags that a `--preset` stands for.
+fn preset_args(name: &str) -> &'static [&'static str] {
+  match name {
+    "prose" => &["--normalize-punctuation", "--markdown-prose"],
+    "code" => &["--ignore-comments", "--case-sensitive", "--min-alpha-ratio", "0.5"],
+    "logs" => &["--mask-digits", "--drop-noise"],
+    _ => {
+      eprintln!("Bad value for --preset, `{name}`: it must be prose, code, or logs.", name = name);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// Gets the value after each use of `flag`, in the order given.
+///
+/// A flag that's the last arg, with no value after it, is an error.
+fn arg_values<'a>(args: &'a [String], flag: &str) -> Vec<&'a str> {
+  let mut values = Vec::new();
+  for (i, s) in args.iter().enumerate() {
+    if s.as_str() == flag {
+      match args.get(i + 1) {
+        Some(value) => values.push(value.as_str()),
+        None => {
+          eprintln!("{flag} needs a value after it.", flag = flag);
+          std::process::exit(1);
+        }
+      }
+    }
+  }
+  values
+}
+
+/// Parses a `--source LABEL=PATH`, where PATH must be a directory.
+fn parse_source(source: &str) -> (String, PathBuf) {
+  match source.split_once('=') {
+    Some((label, path)) if !label.is_empty() && Path::new(path).is_dir() => {
+      (label.to_string(), PathBuf::from(path))
+    }
+    Some((label, _)) if !label.is_empty() => {
+      eprintln!("Bad value for --source, `{source}`: the path isn't a directory.", source = source);
+      std::process::exit(1);
+    }
+    _ => {
+      eprintln!("Bad value for --source, `{source}`: it must be LABEL=PATH.", source = source);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// Reads the `.editorconfig` in the root directory, giving back the globs of
+/// the sections that set a `charset` or an `indent_style`.
+///
+/// Like in EditorConfig, a glob without a `/` matches files by name in any
+/// directory, and one with a `/` matches from the root. Only the root's own
+/// `.editorconfig` is read, and number ranges like `{1..3}` aren't supported.
+fn load_editorconfig(root: &Path) -> GlobSet {
+  let path = root.join(".editorconfig");
+  let text = match std::fs::read_to_string(&path) {
+    Ok(text) => text,
+    Err(e) => {
+      eprintln!("Couldn't read {path}: {e}", path = path.display(), e = e);
+      std::process::exit(1);
+    }
+  };
+  let mut builder = GlobSetBuilder::new();
+  let mut section: Option<&str> = None;
+  let mut added = false;
+  for line in text.lines().map(str::trim) {
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+      continue;
+    }
+    if let Some(header) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+      section = Some(header);
+      added = false;
+      continue;
+    }
+    let key = line.split('=').next().unwrap_or("").trim().to_ascii_lowercase();
+    let pattern = match section {
+      Some(pattern) if !added && (key == "charset" || key == "indent_style") => pattern,
+      _ => continue,
+    };
+    let pattern = match pattern.strip_prefix('/') {
+      Some(from_root) => from_root.to_string(),
+      None if pattern.contains('/') => pattern.to_string(),
+      None => format!("**/{pattern}", pattern = pattern),
+    };
+    match GlobBuilder::new(&pattern).literal_separator(true).build() {
+      Ok(glob) => builder.add(glob),
+      Err(e) => {
+        eprintln!("Bad .editorconfig section `{pattern}`: {e}", pattern = pattern, e = e);
+        std::process::exit(1);
+      }
+    };
+    added = true;
+  }
+  match builder.build() {
+    Ok(globs) => globs,
+    Err(e) => {
+      eprintln!("Couldn't build the .editorconfig patterns: {e}", e = e);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// The directory to walk: `WHIST_ROOT` if it's set (and not empty), otherwise
+/// the current directory.
+fn root_dir() -> PathBuf {
+  match std::env::var_os("WHIST_ROOT").filter(|root| !root.is_empty()) {
+    Some(root) => {
+      let root = PathBuf::from(root);
+      if !root.is_dir() {
+        eprintln!("WHIST_ROOT is set to {root}, which isn't a directory.", root = root.display());
+        std::process::exit(1);
+      }
+      root
+    }
+    None => PathBuf::from("."),
+  }
+}
+
+/// Parses a value given on the command line, with a bad value being an error.
+fn parse_arg<T: std::str::FromStr>(flag: &str, value: &str) -> T
+where
+  T::Err: std::fmt::Display,
+{
+  match value.parse() {
+    Ok(t) => t,
+    Err(e) => {
+      eprintln!("Bad value for {flag}, `{value}`: {e}", flag = flag, value = value, e = e);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// Parses a comma separated list of bucket edges, which must be increasing.
+fn parse_bucket_edges(list: &str) -> Vec<usize> {
+  let edges: Vec<usize> =
+    list.split(',').map(|edge| parse_arg("--bucket-edges", edge.trim())).collect();
+  if edges.windows(2).any(|pair| pair[0] >= pair[1]) || edges.first() == Some(&0) {
+    eprintln!("The --bucket-edges must be positive and increasing, got `{list}`.", list = list);
+    std::process::exit(1);
+  }
+  edges
+}
+
+/// The `--weights` for files matching each glob pattern.
+struct Weights {
+  globs: GlobSet,
+  weights: Vec<f64>,
+}
+impl Weights {
+  /// Reads a weights file, with a glob pattern and a weight on each line.
+  ///
+  /// Blank lines and lines starting with `#` are skipped. Any problem with the
+  /// file is an error.
+  fn load(path: &str) -> Self {
+    let text = match std::fs::read_to_string(path) {
+      Ok(text) => text,
+      Err(e) => {
+        eprintln!("Couldn't read weights file {path}: {e}", path = path, e = e);
+        std::process::exit(1);
+      }
+    };
+    let mut builder = GlobSetBuilder::new();
+    let mut weights = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+      let (pattern, weight) = match line.rsplit_once(char::is_whitespace) {
+        Some((pattern, weight)) => (pattern.trim(), weight),
+        None => {
+          eprintln!("Weights line `{line}` needs a pattern and then a weight.", line = line);
+          std::process::exit(1);
+        }
+      };
+      match Glob::new(pattern) {
+        Ok(glob) => builder.add(glob),
+        Err(e) => {
+          eprintln!("Bad glob pattern `{pattern}`: {e}", pattern = pattern, e = e);
+          std::process::exit(1);
+        }
+      };
+      weights.push(parse_arg("--weights", weight));
+    }
+    match builder.build() {
+      Ok(globs) => Weights { globs, weights },
+      Err(e) => {
+        eprintln!("Couldn't build the weight patterns: {e}", e = e);
+        std::process::exit(1);
+      }
+    }
+  }
+
+  /// The weight of the first pattern the path matches, or 1.0 if none match.
+  fn weight_of(&self, root: &Path, path: &Path) -> f64 {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    match self.globs.matches(path).into_iter().min() {
+      Some(i) => self.weights[i],
+      None => 1.0,
+    }
+  }
+}
+
+/// The `--phrases` to count as single words.
+struct Phrases {
+  root: PhraseNode,
+  /// How many words are in the longest phrase.
+  longest: usize,
+}
+
+/// A node in the trie of phrases, keyed by the next word.
+#[derive(Default)]
+struct PhraseNode {
+  next: HashMap<String, PhraseNode>,
+  /// If the words leading to this node make up a whole phrase.
+  is_phrase: bool,
+}
+
+impl Phrases {
+  /// Reads a phrases file, with one phrase per line.
+  ///
+  /// If `case_sensitive` is false the words are all made lowercase. Lines with
+  /// less than two words are skipped, and not being able to read the file is
+  /// an error.
+  fn load(path: &str, case_sensitive: bool) -> Self {
+    let text = match std::fs::read_to_string(path) {
+      Ok(text) => text,
+      Err(e) => {
+        eprintln!("Couldn't read phrases file {path}: {e}", path = path, e = e);
+        std::process::exit(1);
+      }
+    };
+    let mut phrases = Phrases { root: PhraseNode::default(), longest: 0 };
+    for line in text.lines() {
+      let phrase: Vec<&str> = words(line).collect();
+      if phrase.len() < 2 {
+        continue;
+      }
+      let mut node = &mut phrases.root;
+      for word in phrase.iter() {
+        let word = if case_sensitive { word.to_string() } else { word.to_lowercase() };
+        node = node.next.entry(word).or_default();
+      }
+      node.is_phrase = true;
+      phrases.longest = phrases.longest.max(phrase.len());
+    }
+    phrases
+  }
+
+  /// How many of the words at the front of `upcoming` make up the longest
+  /// phrase, or 0 if they don't start a phrase at all.
+  fn longest_match(&self, upcoming: &VecDeque<&str>, case_sensitive: bool) -> usize {
+    let mut node = &self.root;
+    let mut longest = 0;
+    for (i, word) in upcoming.iter().enumerate() {
+      let next =
+        if case_sensitive { node.next.get(*word) } else { node.next.get(&word.to_lowercase()) };
+      match next {
+        Some(next) => node = next,
+        None => break,
+      }
+      if node.is_phrase {
+        longest = i + 1;
+      }
+    }
+    longest
+  }
+
+  /// Joins up the runs of words that make a phrase into one word, with a
+  /// single space between each part.
+  fn join<'t>(
+    &'t self, mut words: impl Iterator<Item = &'t str> + 't, case_sensitive: bool,
+  ) -> impl Iterator<Item = Cow<'t, str>> + 't {
+    let mut upcoming: VecDeque<&str> = VecDeque::with_capacity(self.longest);
+    std::iter::from_fn(move || {
+      while upcoming.len() < self.longest {
+        match words.next() {
+          Some(word) => upcoming.push_back(word),
+          None => break,
+        }
+      }
+      match self.longest_match(&upcoming, case_sensitive) {
+        0 => upcoming.pop_front().map(Cow::Borrowed),
+        len => Some(Cow::Owned(upcoming.drain(..len).collect::<Vec<&str>>().join(" "))),
+      }
+    })
+  }
+}
+
+/// Reads a JSON object of word counts, like `--also-json` writes, with the
+/// most frequent words first.
+///
+/// Not being able to read or parse the file is an error.
+fn load_json_counts(path: &str) -> Vec<(String, usize)> {
+  let parsed = std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| {
+    serde_json::from_str::<HashMap<String, usize>>(&text).map_err(|e| e.to_string())
+  });
+  match parsed {
+    Ok(counts) => {
+      let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+      counts.sort_unstable_by(|(w1, c1), (w2, c2)| c2.cmp(c1).then_with(|| w1.cmp(w2)));
+      counts
+    }
+    Err(e) => {
+      eprintln!("Couldn't load word counts from {path}: {e}", path = path, e = e);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// Reads a file with one word per line into a set.
+///
+/// If `case_sensitive` is false the words are all made lowercase. Not being
+/// able to read the file is an error.
+fn load_word_list(path: &str, case_sensitive: bool) -> FxHashSet<String> {
+  match std::fs::read_to_string(path) {
+    Ok(s) => s
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty())
+      .map(|word| if case_sensitive { word.to_string() } else { word.to_lowercase() })
+      .collect(),
+    Err(e) => {
+      eprintln!("Couldn't read word list {path}: {e}", path = path, e = e);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// If the word passes the `--dictionary` check (when there is one).
+fn in_dictionary(config: &Config, word: &str) -> bool {
+  match &config.dictionary {
+    None => true,
+    Some(dictionary) => {
+      if config.case_sensitive || !word.chars().any(char::is_uppercase) {
+        dictionary.contains(word)
+      } else {
+        dictionary.contains(&word.to_lowercase())
+      }
+    }
+  }
+}
+
+/// Does everything to a file's text that happens before it's split into words:
+/// `--normalize-punctuation`, `--html`, `--markdown-prose`,
+/// `--ignore-comments`, and `--line-filter`.
+///
+/// The `path` (if any) is used to tell what kind of file it is.
+fn clean_text<'t>(config: &Config, path: Option<&Path>, mut text: Cow<'t, str>) -> Cow<'t, str> {
+  let ext = path.and_then(Path::extension).map(|ext| ext.to_string_lossy());
+  if config.normalize_punctuation {
+    if let Cow::Owned(normalized) = normalize_punctuation(&text) {
+      text = Cow::Owned(normalized);
+    }
+  }
+  if config.html {
+    text = Cow::Owned(strip_html(&text));
+  }
+  if config.markdown_prose {
+    let is_markdown = ext
+      .as_deref()
+      .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"));
+    if is_markdown {
+      text = Cow::Owned(strip_markdown_code(&text));
+    }
+  }
+  if config.ignore_comments {
+    if let Some(style) = ext.as_deref().and_then(CommentStyle::from_extension) {
+      text = Cow::Owned(strip_comments(&text, style));
+    }
+  }
+  if let Some(line_filter) = &config.line_filter {
+    let long_lines = text.lines().filter(|line| line.len() > MAX_LINE_BYTES).count();
+    if long_lines > 0 {
+      eprintln!(
+        "{path}: {long_lines} lines are over {max} bytes, --line-filter checks them in pieces.",
+        path = path.map_or_else(|| "The text".to_string(), |p| p.display().to_string()),
+        long_lines = long_lines,
+        max = MAX_LINE_BYTES
+      );
+    }
+    let lines: Vec<&str> =
+      text.lines().flat_map(line_pieces).filter(|line| line_filter.is_match(line)).collect();
+    text = Cow::Owned(lines.join("\n"));
+  }
+  text
+}
+
+/// Splits the text into the words that get counted, going by
+/// `--separators`, `--social`, `--split-alnum-transitions`, and `--phrases`.
+fn split_words<'t>(
+  config: &'t Config, text: &'t str,
+) -> Box<dyn Iterator<Item = Cow<'t, str>> + 't> {
+  let words = split_tokens(config, text);
+  match &config.phrases {
+    Some(phrases) => Box::new(phrases.join(words, config.case_sensitive)),
+    None => Box::new(words.map(Cow::Borrowed)),
+  }
+}
+
+/// Splits the text the way [`split_words`] does, but without joining
+/// `--phrases`, so every word is still a slice of `text`.
+fn split_tokens<'t>(config: &'t Config, text: &'t str) -> Box<dyn Iterator<Item = &'t str> + 't> {
+  let words: Box<dyn Iterator<Item = &str>> = match &config.separators {
+    Some(separators) => {
+      Box::new(text.split(move |c| separators.contains(&c)).filter(|w| !w.is_empty()))
+    }
+    None if config.social => Box::new(social_words(text)),
+    None => Box::new(words(text)),
+  };
+  if config.split_alnum_transitions {
+    Box::new(words.flat_map(split_alnum_transitions))
+  } else {
+    words
+  }
+}
+
+/// Changes the word with `--transliterate`, `--strip-prefix`,
+/// `--mask-digits`, and `--fold-confusables`, in that order.
+///
+/// With `--fold-confusables` the folded word (its skeleton) is only used to
+/// tell which words look alike, since it's often not a real spelling (`modern`
+/// folds to `rnodern`). The word given back is the first spelling seen with
+/// that skeleton, which `spellings` keeps track of.
+///
+/// A word with nothing left after transliterating gives `None`.
+fn transform_word<'w>(
+  config: &Config, intern: &mut HashSet<&'static str>, spellings: &mut Spellings,
+  word: Cow<'w, str>,
+) -> Option<Cow<'w, str>> {
+  let word: Cow<str> =
+    if config.transliterate { Cow::Owned(deunicode(&word).trim().to_string()) } else { word };
+  if word.is_empty() {
+    return None;
+  }
+  let word = match strip_any_prefix(&word, &config.strip_prefixes, config.case_sensitive) {
+    stripped if stripped.len() == word.len() => word,
+    stripped => Cow::Owned(stripped.to_string()),
+  };
+  let word = match config.mask_digits.map(|placeholder| mask_digits(&word, placeholder)) {
+    Some(Cow::Owned(masked)) => Cow::Owned(masked),
+    _ => word,
+  };
+  if config.fold_confusables {
+    let spelling =
+      *spellings.entry(fold_confusables(&word)).or_insert_with(|| intern_word(intern, &word));
+    return Some(Cow::Borrowed(spelling));
+  }
+  Some(word)
+}
+
+/// With `--fold-confusables`, the first spelling seen of each skeleton.
+type Spellings = HashMap<String, &'static str>;
+
+/// Prints each word that the text is split into, and what the counting would
+/// do with it.
+///
+/// This goes through the same steps as counting a file does (without a path,
+/// so the flags that go by file type don't apply). What happens to the totals
+/// afterwards, such as `--min-count` or `--drop-noise`, isn't shown.
+fn explain(config: &Config, text: &str) {
+  let mut text = clean_text(config, None, Cow::Borrowed(text));
+  if let Cow::Owned(cleaned) = &text {
+    println!("Text {cleaned:?} is what gets split up", cleaned = cleaned);
+  }
+  if let Some(pattern) = &config.extract_urls {
+    let (rest, found) = extract_urls(&text, pattern);
+    for url in found {
+      let kind = if url.contains("://") { "URL" } else { "path" };
+      println!("Found {url:?}: counted as a {kind}", url = url, kind = kind);
+    }
+    text = Cow::Owned(rest);
+  }
+  let mut intern: HashSet<&'static str> = HashSet::new();
+  let mut spellings = Spellings::new();
+  for piece in split_words(config, &text) {
+    let original = piece.to_string();
+    let word = match transform_word(config, &mut intern, &mut spellings, piece) {
+      Some(word) => word,
+      None => {
+        println!(
+          "Letters {piece:?}: skipped, nothing left after --transliterate",
+          piece = original
+        );
+        continue;
+      }
+    };
+    if config.min_alpha_ratio.is_some_and(|ratio| alpha_ratio(&word) < ratio) {
+      println!("Letters {piece:?}: skipped, under the --min-alpha-ratio", piece = original);
+    } else if !in_dictionary(config, &word) {
+      println!("Letters {piece:?}: skipped, not in the dictionary", piece = original);
+    } else {
+      let word = if config.shapes { Cow::Owned(word_shape(&word)) } else { word };
+      if word == original {
+        println!("Letters {piece:?}: counted", piece = original);
+      } else {
+        println!("Letters {piece:?}: counted as {word:?}", piece = original, word = word);
+      }
+    }
+  }
+}
+
+/// Runs `run` over and over, once at the start and again after each burst of
+/// changes to the files under the root directory.
+#[cfg(feature = "watch")]
+fn watch(config: &Config, intern: &mut HashSet<&'static str>) {
+  let (tx, rx) = std::sync::mpsc::channel();
+  let mut watcher = match notify::recommended_watcher(tx) {
+    Ok(watcher) => watcher,
+    Err(e) => {
+      eprintln!("Couldn't start watching for changes: {e}", e = e);
+      std::process::exit(1);
+    }
+  };
+  if let Err(e) = watcher.watch(&config.root, RecursiveMode::Recursive) {
+    eprintln!("Couldn't watch {root}: {e}", root = config.root.display(), e = e);
+    std::process::exit(1);
+  }
+  // whist's own output files changing doesn't count, or every re-count would
+  // set off another one.
+  let is_change = |result: &notify::Result<notify::Event>| match result {
+    Ok(event) => {
+      (event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove())
+        && event.paths.iter().any(|path| !is_output_file(config, path))
+    }
+    Err(e) => {
+      eprintln!("Error while watching: {e}", e = e);
+      false
+    }
+  };
+  loop {
+    // clear the screen and put the cursor in the top left.
+    print!("\x1B[2J\x1B[H");
+    // a failed run has already said what went wrong, and the next change
+    // might fix it.
+    let _ = run(config, intern);
+    // wait for any change at all, then keep eating events until things have
+    // been quiet for a moment so that a burst of saves is just one re-count.
+    loop {
+      match rx.recv() {
+        Ok(result) if is_change(&result) => break,
+        Ok(_) => continue,
+        Err(_) => return,
+      }
+    }
+    while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+  }
+}
+
+/// A key of the word count map.
+///
+/// The key type decides if words that differ only by case get counted together
+/// (`UniCase`) or separately (plain `&str`).
+trait WordKey: Ord + Hash + Copy {
+  fn from_word(word: &'static str) -> Self;
+  fn word(self) -> &'static str;
+}
+impl WordKey for &'static str {
+  fn from_word(word: &'static str) -> Self {
+    word
+  }
+  fn word(self) -> &'static str {
+    self
+  }
+}
+impl WordKey for UniCase<&'static str> {
+  fn from_word(word: &'static str) -> Self {
+    UniCase::new(word)
+  }
+  fn word(self) -> &'static str {
+    self.into_inner()
+  }
+}
+
+/// Gets the `'static` copy of a word, leaking a new one if it's not yet in the
+/// intern set.
+///
+/// This is the only time a word gets hashed when just counting: the totals
+/// are a `BTreeMap`, which compares the `'static` keys instead of hashing
+/// them. The keys are `Copy` so that all the other per-word maps (per file,
+/// per group, per document, the reverse index, and so on) can share them,
+/// and the set outliving each run keeps `--watch` from leaking the same word
+/// again.
+fn intern_word(intern: &mut HashSet<&'static str>, word: &str) -> &'static str {
+  intern.get(word).copied().unwrap_or_else(|| {
+    let leaked: &'static str = Box::leak(String::from(word).into_boxed_str());
+    intern.insert(leaked);
+    leaked
+  })
+}
+
+/// A run that stopped because of an error, which has already been printed.
+struct RunFailed;
+
+/// Walks the root directory, counts up all the words, and prints them.
+///
+/// Errors that should make whist exit with a failure come back as
+/// [`RunFailed`] instead of exiting right away, so that `--watch` can keep
+/// going.
+fn run(config: &Config, intern: &mut HashSet<&'static str>) -> Result<(), RunFailed> {
+  // shapes are all about case, so they always need to be case sensitive.
+  let result = if config.case_sensitive || config.shapes {
+    run_with::<&'static str>(config, intern)
+  } else {
+    run_with::<UniCase<&'static str>>(config, intern)
+  };
+  if config.intern_stats {
+    let bytes: usize = intern.iter().map(|word| word.len()).sum();
+    eprintln!(
+      "Interned {words} words, {bytes} bytes of text leaked ({average:.1} bytes per word), \
+      with a table of {capacity} slots.",
+      words = intern.len(),
+      bytes = bytes,
+      average = if intern.is_empty() { 0.0 } else { bytes as f64 / intern.len() as f64 },
+      capacity = intern.capacity()
+    );
+  }
+  result
+}
+
+fn run_with<K: WordKey>(
+  config: &Config, intern: &mut HashSet<&'static str>,
+) -> Result<(), RunFailed> {
+  const TEN_MEGABYTES: usize = 10 * 1024 * 1024;
+  let mut buf = Vec::with_capacity(TEN_MEGABYTES);
+  // for `--mem-report`: the biggest `buf` has been, how many reads made it
+  // bigger, and how many bytes were read in all.
+  let mut buf_peak = buf.capacity();
+  let mut buf_grows: usize = 0;
+  let mut bytes_read: usize = 0;
+  // with `--clipboard`, the text on the clipboard, which is counted instead of
+  // any files.
+  #[cfg(feature = "clipboard")]
+  let clipboard = if config.clipboard {
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+      Ok(text) => Some(text),
+      Err(e) => {
+        eprintln!("Couldn't read the clipboard: {e}", e = e);
+        return Err(RunFailed);
+      }
+    }
+  } else {
+    None
+  };
+  #[cfg(not(feature = "clipboard"))]
+  let clipboard: Option<String> = None;
+  let mut word_counts: BTreeMap<K, usize> = BTreeMap::new();
+  // the last document (see `--document-unit`) each word was seen in, and how
+  // many documents that word has been seen in so far.
+  let mut doc_counts: HashMap<K, (usize, usize)> = HashMap::new();
+  // with `--document-unit directory`, the number given to each directory, and
+  // every word and directory pair seen.
+  let mut document_ids: HashMap<String, usize> = HashMap::new();
+  let mut document_words: HashSet<(K, usize)> = HashSet::new();
+  let mut file_index: usize = 0;
+  // how many files were actually opened and read.
+  let mut files_read: usize = 0;
+  let mut biggest_word: usize = 0;
+  let mut non_dictionary_words: usize = 0;
+  // how many of the words counted were all caps, and how many words there were.
+  let mut caps_words: usize = 0;
+  let mut total_words: usize = 0;
+  // each file, and how many words it added that no earlier file had.
+  let mut vocab_growth: Vec<(PathBuf, usize)> = Vec::new();
+  // total tokens from the files last modified in each year (if known).
+  let mut year_tokens: BTreeMap<Option<i64>, usize> = BTreeMap::new();
+  // the path, bytes, chars, and words of each file.
+  let mut file_stats: Vec<(PathBuf, usize, usize, usize)> = Vec::new();
+  // in weighted mode, the sum of the weights of every file a word was in.
+  let mut weighted_counts: BTreeMap<K, f64> = BTreeMap::new();
+  // word counts for just the current file.
+  let mut file_counts: HashMap<K, usize> = HashMap::new();
+  // with `--positions`, the sum of each word's token indexes in the current
+  // file and how many times it was in the file, then the sum of its positions
+  // (as a fraction of the way through each file) and how many times it was
+  // seen in all the files.
+  let mut file_positions: HashMap<K, (usize, usize)> = HashMap::new();
+  let mut positions: HashMap<K, (f64, usize)> = HashMap::new();
+  // with `--dedupe-inodes`, the device and inode of each file counted so far.
+  let mut seen_files: HashSet<(u64, u64)> = HashSet::new();
+  // with `--count-emoji`, the count of each emoji (as it was written).
+  let mut emoji_counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+  let mut url_counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+  let mut path_counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+  // with `--introduction-curve`, the words seen so far in the current file,
+  // and then for each word number the sum of how many different words the
+  // files had seen by then, and how many files got that far.
+  let mut file_introduced: HashSet<K> = HashSet::new();
+  let mut curve: Vec<(usize, usize)> = Vec::new();
+  // with `--trailing-whitespace`, each file with lines that end in spaces or
+  // tabs, and how many lines do.
+  let mut trailing_whitespace: Vec<(PathBuf, usize)> = Vec::new();
+  // with `--reverse-index`, the files each word was in (as indexes into
+  // `indexed_paths`) and how many times it was in each.
+  let mut reverse_index: BTreeMap<K, Vec<(usize, usize)>> = BTreeMap::new();
+  let mut indexed_paths: Vec<String> = Vec::new();
+  // with `--around-symbol`, the counts of the words just before and just
+  // after the symbol.
+  let mut before_symbol: BTreeMap<K, usize> = BTreeMap::new();
+  let mut after_symbol: BTreeMap<K, usize> = BTreeMap::new();
+  // with `--acronyms`, the count of each acronym, in the case it was written.
+  let mut acronym_counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+  // with `--sentences`, the number of words in each sentence.
+  let mut sentence_lengths: Vec<usize> = Vec::new();
+  // with `--doubled-words`, the path and word number of each word that was the
+  // same as the word before it, along with both words as they were written.
+  let mut doubled_words: Vec<(PathBuf, usize, [String; 2])> = Vec::new();
+  // word counts for each directory at the top of the walk, or for each
+  // language with `--by-language`.
+  let mut group_counts: BTreeMap<String, BTreeMap<K, usize>> = BTreeMap::new();
+
+  let mut started = Instant::now();
+  // `--time-decay` ages files relative to this, so every file in the run
+  // uses the same "now".
+  let started_at = SystemTime::now();
+  let mut stopped_early = false;
+  // with `--halt-on-error`, if the walk stopped because of an error.
+  let mut halted = false;
+  // with `--approx-top` the first pass only fills in the summary, and the
+  // second pass (if any) counts just the words the summary kept.
+  let mut summary = config.approx_top.map(|n| MisraGries::new(n * APPROX_COUNTERS_PER_WORD));
+  let mut candidates: Option<HashSet<String>> = None;
+  let mut spiller = config.external_merge.map(Spiller::new);
+  let mut spellings = Spellings::new();
+  // with `--checkpoint`, the files that have been counted so far (including
+  // by the run that saved the checkpoint), and how many since the last save.
+  let mut checkpointed: HashSet<String> = HashSet::new();
+  let mut since_checkpoint: usize = 0;
+  if let Some((path, _)) = &config.checkpoint {
+    if Path::new(path).exists() {
+      match load_checkpoint(path) {
+        Ok(checkpoint) => {
+          eprintln!(
+            "Resuming from {path}, skipping the {files} files already counted.",
+            path = path,
+            files = checkpoint.files.len()
+          );
+          checkpointed = checkpoint.files;
+          for (word, count) in checkpoint.counts {
+            let word = intern_word(intern, &word);
+            biggest_word = biggest_word.max(word.len());
+            *word_counts.entry(K::from_word(word)).or_insert(0) += count as usize;
+          }
+        }
+        Err(e) => {
+          eprintln!("Couldn't load the checkpoint {path}: {e}", path = path, e = e);
+          return Err(RunFailed);
+        }
+      }
+    }
+  }
+  let passes = if config.approx_top.is_some() && config.approx_exact { 2 } else { 1 };
+  for pass in 0..passes {
+    if pass > 0 {
+      candidates = summary.as_ref().map(|summary| summary.counters.keys().cloned().collect());
+      // the exact pass goes over all the same files again, with its own
+      // `--timeout`.
+      file_index = 0;
+      seen_files.clear();
+      started = Instant::now();
+      stopped_early = false;
+    }
+    // the `--source` label (if any) goes along with each file.
+    let mut count_file = |label: Option<&str>, p: PathBuf| {
+      if config.max_files.is_some_and(|max| file_index >= max)
+        || config.timeout.is_some_and(|timeout| started.elapsed() >= timeout)
+      {
+        stopped_early = true;
+        return ControlFlow::Break(());
+      }
+      // The clipboard isn't a file, so it skips the path and metadata checks.
+      if clipboard.is_none() {
+        if is_output_file(config, &p) {
+          return ControlFlow::Continue(());
+        }
+        if config.checkpoint.is_some() && checkpointed.contains(&*p.to_string_lossy()) {
+          return ControlFlow::Continue(());
+        }
+        if config.dedupe_inodes {
+          if let Some(id) = file_id(&p) {
+            if !seen_files.insert(id) {
+              return ControlFlow::Continue(());
+            }
+          }
+        }
+        if let Some(ext) = p.extension() {
+          let ext = UniCase::new(ext.to_string_lossy().into_owned());
+          if config.exclude_extensions.contains(&ext) {
+            return ControlFlow::Continue(());
+          }
+        }
+        if let Some(interpreter) = config.shebang.as_deref().filter(|_| p.extension().is_none()) {
+          match read_shebang(&p) {
+            Ok(Some(shebang)) => {
+              if !interpreter.is_empty() && !shebang_interpreter(&shebang).starts_with(interpreter)
+              {
+                return ControlFlow::Continue(());
+              }
+            }
+            Ok(None) => return ControlFlow::Continue(()),
+            Err(e) => {
+              eprintln!("Couldn't check {path} for a shebang: {e}", path = p.display(), e = e);
+              return ControlFlow::Continue(());
+            }
+          }
+        }
+        if let Some(text_files) = &config.editorconfig {
+          if !text_files.is_match(p.strip_prefix(&config.root).unwrap_or(&p)) {
+            return ControlFlow::Continue(());
+          }
+        }
+        if let Some(cutoff) = config.since {
+          match std::fs::metadata(&p).and_then(|m| m.modified()) {
+            Ok(modified) if modified < cutoff => return ControlFlow::Continue(()),
+            Ok(_) => (),
+            Err(e) if config.skip_unknown_mtime => {
+              eprintln!(
+                "Skipping {path}, can't get its modified time: {e}",
+                path = p.display(),
+                e = e
+              );
+              return ControlFlow::Continue(());
+            }
+            Err(e) => {
+              eprintln!(
+                "Counting {path}, can't get its modified time: {e}",
+                path = p.display(),
+                e = e
+              )
+            }
+          }
+        }
+      }
+      let unique_before = word_counts.len();
+      let mut file_tokens: usize = 0;
+      // the bytes and chars read, once the file's been read.
+      let mut file_size: Option<(usize, usize)> = None;
+      // with `--by-language` the group is only known once the file's read.
+      let mut group = if config.by_directory {
+        Some(group_counts.entry(top_directory(&config.root, &p)).or_default())
+      } else {
+        label.map(|label| group_counts.entry(label.to_string()).or_default())
+      };
+      let mut file_weight = match &config.weights {
+        Some(weights) => weights.weight_of(&config.root, &p),
+        None => 1.0,
+      };
+      if let Some(half_life) = config.time_decay.filter(|_| clipboard.is_none()) {
+        match std::fs::metadata(&p).and_then(|m| m.modified()) {
+          Ok(modified) => {
+            let age = started_at.duration_since(modified).unwrap_or(Duration::ZERO);
+            file_weight *= 0.5_f64.powf(age.as_secs_f64() / half_life.as_secs_f64());
+          }
+          Err(e) => eprintln!(
+            "Not decaying {path}, can't get its modified time: {e}",
+            path = p.display(),
+            e = e
+          ),
+        }
+      }
+      let read = match &clipboard {
+        Some(text) => {
+          buf.extend_from_slice(text.as_bytes());
+          Some(text.len())
+        }
+        None => match std::fs::File::open(&p) {
+          // files that got deleted between the `read_dir` and now are just normal
+          // churn on a live system, so they're only reported when asked for.
+          Err(e) if e.kind() == std::io::ErrorKind::NotFound && !config.verbose => None,
+          Err(e) => {
+            eprintln!("Couldn't open {path}: {e}", path = p.display(), e = e);
+            if config.halt_on_error {
+              halted = true;
+              return ControlFlow::Break(());
+            }
+            None
+          }
+          Ok(mut f) => match read_file(&mut f, config, &mut buf) {
+            Err(e) => {
+              eprintln!("Error while reading {path}: {e}", path = p.display(), e = e);
+              if config.halt_on_error {
+                halted = true;
+                return ControlFlow::Break(());
+              }
+              None
+            }
+            Ok(byte_count_read) => Some(byte_count_read),
+          },
+        },
+      };
+      if let Some(byte_count_read) = read {
+        files_read += 1;
+        let document = match config.document_unit {
+          DocumentUnit::File => file_index,
+          DocumentUnit::Directory => {
+            let next_id = document_ids.len();
+            *document_ids.entry(top_directory(&config.root, &p)).or_insert(next_id)
+          }
+          DocumentUnit::All => 0,
+        };
+        if buf.capacity() > buf_peak {
+          buf_peak = buf.capacity();
+          buf_grows += 1;
+        }
+        bytes_read += byte_count_read;
+        if config.head_bytes.is_some() {
+          // don't let the cut off split a char in half.
+          buf.truncate(complete_utf8_len(&buf));
+        }
+        if config.offset > 0 {
+          // or the offset either.
+          let partial = buf.iter().take(3).take_while(|&&b| b & 0xC0 == 0x80).count();
+          buf.drain(..partial);
+        }
+        if config.report_invalid_bytes || config.halt_on_error {
+          let invalid = invalid_utf8_bytes(&buf);
+          if invalid > 0 {
+            eprintln!(
+              "Skipped {invalid} invalid UTF-8 bytes in {path}",
+              invalid = invalid,
+              path = p.display()
+            );
+            if config.halt_on_error {
+              halted = true;
+              return ControlFlow::Break(());
+            }
+          }
+        }
+        let text = buf.to_str_lossy();
+        if config.trailing_whitespace {
+          let lines = text.lines().filter(|line| line.ends_with([' ', '\t'])).count();
+          if lines > 0 {
+            trailing_whitespace.push((p.clone(), lines));
+          }
+        }
+        if config.stats_per_file {
+          file_size = Some((byte_count_read, text.chars().count()));
+        }
+        let mut text = clean_text(config, Some(&p), text);
+        if let Some(pattern) = &config.extract_urls {
+          let (rest, found) = extract_urls(&text, pattern);
+          for url in found {
+            let counts = if url.contains("://") { &mut url_counts } else { &mut path_counts };
+            *counts.entry(intern_word(intern, url)).or_insert(0) += 1;
+          }
+          text = Cow::Owned(rest);
+        }
+        if config.count_emoji {
+          // a char like `©` is only an emoji when it's shown as one: either
+          // it's shown that way by default, or a U+FE0F asks for it to be.
+          let emoji_presentation = CodePointSetData::new::<EmojiPresentation>();
+          for grapheme in text.as_bytes().graphemes() {
+            let shown_as_emoji =
+              grapheme.chars().any(|c| c == '\u{FE0F}' || emoji_presentation.contains(c));
+            if shown_as_emoji && emojis::get(grapheme).is_some() {
+              *emoji_counts.entry(intern_word(intern, grapheme)).or_insert(0) += 1;
+            }
+          }
+        }
+        if config.sentences {
+          sentence_lengths
+            .extend(sentences(&text).map(|sentence| words(sentence).count()).filter(|&n| n > 0));
+        }
+        if let Some(symbol) = &config.around_symbol {
+          let (before, after) = words_around(config, &text, symbol);
+          for (words, counts) in [(before, &mut before_symbol), (after, &mut after_symbol)] {
+            for word in words {
+              let word = match transform_word(config, intern, &mut spellings, Cow::Borrowed(word)) {
+                Some(word) => word,
+                None => continue,
+              };
+              let key = K::from_word(intern_word(intern, &word));
+              *counts.entry(key).or_insert(0) += 1;
+            }
+          }
+        }
+        if config.by_language {
+          group = Some(group_counts.entry(language_of(&text).to_string()).or_default());
+        }
+        let words = split_words(config, &text);
+        // with `--doubled-words`, the word before this one and how many words
+        // into the file this one is.
+        let mut previous_word: Option<String> = None;
+        let mut word_number: usize = 0;
+        for word in words {
+          let word = match transform_word(config, intern, &mut spellings, word) {
+            Some(word) => word,
+            None => continue,
+          };
+          if config.doubled_words {
+            word_number += 1;
+            if let Some(previous) = previous_word.take() {
+              let doubled = if config.case_sensitive {
+                previous == word
+              } else {
+                UniCase::new(&*previous) == UniCase::new(&*word)
+              };
+              if doubled {
+                doubled_words.push((p.clone(), word_number, [previous, word.to_string()]));
+              }
+            }
+            previous_word = Some(word.to_string());
+          }
+          if config.min_alpha_ratio.is_some_and(|ratio| alpha_ratio(&word) < ratio) {
+            continue;
+          }
+          if !in_dictionary(config, &word) {
+            non_dictionary_words += 1;
+            continue;
+          }
+          if config.acronyms && is_acronym(&word) {
+            *acronym_counts.entry(intern_word(intern, &word)).or_insert(0) += 1;
+          }
+          let word = if config.shapes { Cow::Owned(word_shape(&word)) } else { word };
+          let word = match summary.as_mut() {
+            None => word,
+            Some(summary) => {
+              let word = if config.case_sensitive { word } else { Cow::Owned(word.to_lowercase()) };
+              match &candidates {
+                None => {
+                  summary.add(&word);
+                  continue;
+                }
+                Some(candidates) if !candidates.contains(&*word) => continue,
+                Some(_) => word,
+              }
+            }
+          };
+          if let Some(spiller) = spiller.as_mut() {
+            let word = if config.case_sensitive { word } else { Cow::Owned(word.to_lowercase()) };
+            biggest_word = biggest_word.max(word.len());
+            if let Err(e) = spiller.add(word) {
+              eprintln!("Couldn't save counts to a spill file: {e}", e = e);
+              halted = true;
+              return ControlFlow::Break(());
+            }
+            continue;
+          }
+          // Note(Lokathor): Every transform of the word has to be done by
+          // this point. Words that transform to the same thing need to land
+          // on the same key here, otherwise they'd print as separate lines
+          // with the same text. Only `--redact` changes words after this,
+          // when printing.
+          let interned_letters: &'static str = intern_word(intern, &word);
+          biggest_word = biggest_word.max(interned_letters.len());
+          let key = K::from_word(interned_letters);
+          // with `--cap-per-file`, a word past the cap still counts for
+          // this file's stats but not for the totals.
+          let mut capped = false;
+          if config.repeat_threshold.is_some()
+            || config.cap_per_file.is_some()
+            || config.reverse_index.is_some()
+          {
+            let in_file = file_counts.entry(key).or_insert(0);
+            *in_file += 1;
+            capped = config.cap_per_file.is_some_and(|cap| *in_file > cap);
+          }
+          if !capped {
+            *word_counts.entry(key).or_insert(0) += 1;
+          }
+          if config.positions {
+            let (index_sum, seen) = file_positions.entry(key).or_insert((0, 0));
+            *index_sum += file_tokens;
+            *seen += 1;
+          }
+          file_tokens += 1;
+          if config.introduction_curve {
+            file_introduced.insert(key);
+            if curve.len() < file_tokens {
+              curve.push((0, 0));
+            }
+            let (unique_sum, files) = &mut curve[file_tokens - 1];
+            *unique_sum += file_introduced.len();
+            *files += 1;
+          }
+          if config.caps_ratio {
+            total_words += 1;
+            if is_all_caps(&word) {
+              caps_words += 1;
+            }
+          }
+          if (config.weights.is_some() || config.time_decay.is_some()) && !capped {
+            *weighted_counts.entry(key).or_insert(0.0) += file_weight;
+          }
+          if let Some(group) = group.as_mut().filter(|_| !capped) {
+            *group.entry(key).or_insert(0) += 1;
+          }
+          if config.with_doc_count || config.in_all_files {
+            let (last_document, documents) = doc_counts.entry(key).or_insert((usize::MAX, 0));
+            // the files of a directory don't all come one after the other in
+            // the walk, so every directory a word was in gets remembered.
+            let new_document = match config.document_unit {
+              DocumentUnit::Directory => document_words.insert((key, document)),
+              _ => *last_document != document,
+            };
+            if new_document {
+              *last_document = document;
+              *documents += 1;
+            }
+          }
+        }
+      }
+      buf.clear();
+      file_index += 1;
+      for (key, (index_sum, seen)) in file_positions.drain() {
+        let (position_sum, total_seen) = positions.entry(key).or_insert((0.0, 0));
+        *position_sum += index_sum as f64 / file_tokens as f64;
+        *total_seen += seen;
+      }
+      if config.reverse_index.is_some() && !file_counts.is_empty() {
+        for (&key, &count) in file_counts.iter() {
+          reverse_index.entry(key).or_default().push((indexed_paths.len(), count));
+        }
+        indexed_paths.push(p.display().to_string());
+      }
+      if let Some(threshold) = config.repeat_threshold {
+        if let Some((key, count)) = file_counts.drain().max_by_key(|&(_, count)| count) {
+          let percent = 100.0 * count as f64 / file_tokens as f64;
+          if percent > threshold {
+            eprintln!(
+              "{path}: `{word}` is {percent:.1}% of the {tokens} words",
+              path = p.display(),
+              word = shown_word(config, key.word()),
+              percent = percent,
+              tokens = file_tokens
+            );
+          }
+        }
+      }
+      file_counts.clear();
+      file_introduced.clear();
+      if config.by_mtime_year {
+        let year = std::fs::metadata(&p).and_then(|m| m.modified()).ok().map(year_of);
+        *year_tokens.entry(year).or_insert(0) += file_tokens;
+      }
+      if let Some((bytes, chars)) = file_size {
+        file_stats.push((p.clone(), bytes, chars, file_tokens));
+      }
+      if let Some((path, every)) = &config.checkpoint {
+        checkpointed.insert(p.to_string_lossy().into_owned());
+        since_checkpoint += 1;
+        if since_checkpoint >= *every {
+          since_checkpoint = 0;
+          if let Err(e) = save_checkpoint(path, &checkpointed, &word_counts) {
+            eprintln!("Couldn't save the checkpoint {path}: {e}", path = path, e = e);
+          }
+        }
+      }
+      if config.vocab_growth {
+        vocab_growth.push((p, word_counts.len() - unique_before));
+      }
+      ControlFlow::Continue(())
+    };
+    let walked = if clipboard.is_some() {
+      count_file(None, PathBuf::from("<clipboard>"))
+    } else if config.sources.is_empty() {
+      recursive_read_dir(&config.root, config.sorted, config.halt_on_error, |p| count_file(None, p))
+    } else {
+      config.sources.iter().try_for_each(|(label, path)| {
+        recursive_read_dir(path, config.sorted, config.halt_on_error, |p| {
+          count_file(Some(label), p)
+        })
+      })
+    };
+    // the walk only stops early for `--max-files`, `--timeout`, or an error.
+    if walked.is_break() && !stopped_early {
+      halted = true;
+    }
+    if halted {
+      break;
+    }
+  }
+  if halted {
+    return Err(RunFailed);
+  }
+  if let Some((path, _)) = &config.checkpoint {
+    // a run cut short saves where it got to, and a finished one doesn't need
+    // its checkpoint any more.
+    let result = if stopped_early {
+      save_checkpoint(path, &checkpointed, &word_counts)
+    } else {
+      match std::fs::remove_file(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        other => other,
+      }
+    };
+    if let Err(e) = result {
+      eprintln!("Couldn't update the checkpoint {path}: {e}", path = path, e = e);
+    }
+  }
+  if config.mem_report {
+    eprintln!(
+      "Read {bytes} bytes from {files} files. The read buffer started at {start} bytes, \
+      grew on {grows} reads, and peaked at {peak} bytes.",
+      bytes = bytes_read,
+      files = files_read,
+      start = TEN_MEGABYTES,
+      grows = buf_grows,
+      peak = buf_peak
+    );
+  }
+  if stopped_early {
+    eprintln!(
+      "Stopped after {files} files because of --max-files or --timeout, the results are partial.",
+      files = file_index
+    );
+  }
+
+  if let (Some(n), Some(summary)) = (config.approx_top, &summary) {
+    let mut top: Vec<(&str, usize)> = if config.approx_exact {
+      word_counts.iter().map(|(key, &count)| (key.word(), count)).collect()
+    } else {
+      summary.counters.iter().map(|(word, &count)| (word.as_str(), count)).collect()
+    };
+    top.sort_unstable_by(|(w1, c1), (w2, c2)| c2.cmp(c1).then_with(|| w1.cmp(w2)));
+    top.truncate(n);
+    let biggest_word =
+      top.iter().map(|(word, _)| shown_word(config, word).len()).max().unwrap_or(0);
+    let end = if config.print0 { '\0' } else { '\n' };
+    for (word, count) in top.iter() {
+      if config.words_only {
+        print!("{word}{end}", word = shown_word(config, word), end = end);
+        continue;
+      }
+      print!(
+        "{word:>biggest_word$}: {count}{end}",
+        word = shown_word(config, word),
+        count = count,
+        biggest_word = biggest_word,
+        end = end
+      );
+    }
+    if !config.approx_exact {
+      eprintln!(
+        "These counts are approximate, each could be up to {error} below the real count. \
+        Use --approx-exact for exact counts.",
+        error = summary.decrements
+      );
+    }
+    return Ok(());
+  }
+
+  if let Some(spiller) = spiller.as_mut() {
+    let end = if config.print0 { '\0' } else { '\n' };
+    let printed = spiller.spill().and_then(|()| {
+      let files: Vec<std::fs::File> =
+        spiller.files.iter().map(std::fs::File::open).collect::<std::io::Result<_>>()?;
+      let mut out = counts_output(config);
+      for record in binary::merge_all(files) {
+        let (word, count) = record?;
+        if config.words_only {
+          write!(out, "{word}{end}", word = shown_word(config, &word), end = end)?;
+        } else {
+          write!(
+            out,
+            "{word:>biggest_word$}: {count}{end}",
+            word = shown_word(config, &word),
+            count = count,
+            biggest_word = if config.redact { REDACTED_LEN } else { biggest_word },
+            end = end
+          )?;
+        }
+      }
+      out.flush()
+    });
+    match printed {
+      Ok(()) => (),
+      Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => (),
+      Err(e) => eprintln!("Couldn't merge the spilled counts: {e}", e = e),
+    }
+    return Ok(());
+  }
+
+  // an `--update` file that isn't there yet just starts out empty.
+  let update_path = config.update.iter().filter(|path| Path::new(path).exists());
+  for path in config.load_binary.iter().chain(update_path) {
+    let loaded = std::fs::File::open(path).and_then(|f| {
+      for record in binary::read(f) {
+        let (word, count) = record?;
+        let word = intern_word(intern, &word);
+        biggest_word = biggest_word.max(word.len());
+        *word_counts.entry(K::from_word(word)).or_insert(0) += count as usize;
+      }
+      Ok(())
+    });
+    if let Err(e) = loaded {
+      eprintln!("Couldn't load binary counts from {path}: {e}", path = path, e = e);
+      return Err(RunFailed);
+    }
+  }
+  if config.save_binary.is_some() || config.update.is_some() {
+    let mut rows: Vec<(&str, u64)> =
+      word_counts.iter().map(|(key, &count)| (key.word(), count as u64)).collect();
+    rows.sort_unstable_by_key(|&(word, _)| word);
+    for path in config.save_binary.iter().chain(config.update.iter()) {
+      if let Err(e) = save_binary(path, &rows) {
+        eprintln!("Couldn't save binary counts to {path}: {e}", path = path, e = e);
+      }
+    }
+  }
+
+  if let Some(path) = &config.reverse_index {
+    if let Err(e) = write_reverse_index(config, path, &reverse_index, &indexed_paths) {
+      eprintln!("Couldn't write the reverse index to {path}: {e}", path = path, e = e);
+    }
+  }
+
+  #[cfg(feature = "sqlite")]
+  if let Some(path) = &config.sqlite {
+    let rows = word_counts.iter().map(|(key, &count)| (shown_word(config, key.word()), count));
+    if let Err(e) = write_sqlite(path, rows, config.sqlite_add) {
+      eprintln!("Couldn't write the counts to {path}: {e}", path = path, e = e);
+    }
+  }
+
+  if config.dictionary.is_some() {
+    let total = non_dictionary_words + word_counts.values().sum::<usize>();
+    eprintln!(
+      "{non} of {total} words ({percent:.1}%) were not in the dictionary.",
+      non = non_dictionary_words,
+      total = total,
+      percent = if total == 0 { 0.0 } else { 100.0 * non_dictionary_words as f64 / total as f64 }
+    );
+  }
+
+  if let Some(min) = config.min_total {
+    let total: usize = word_counts.values().sum();
+    if total < min {
+      eprintln!(
+        "Only found {total} words, less than the --min-total of {min}.",
+        total = total,
+        min = min
+      );
+      return Err(RunFailed);
+    }
+  }
+
+  if let Some((min_length, max_count)) = config.drop_noise {
+    word_counts
+      .retain(|key, &mut count| count > max_count || key.word().chars().count() < min_length);
+  }
+  if config.in_all_files {
+    let documents = match config.document_unit {
+      DocumentUnit::File => files_read,
+      DocumentUnit::Directory => document_ids.len(),
+      DocumentUnit::All => files_read.min(1),
+    };
+    word_counts.retain(|key, _| doc_counts.get(key).is_some_and(|&(_, docs)| docs == documents));
+  }
+  if config.print_zero {
+    if let Some(dictionary) = &config.dictionary {
+      for word in dictionary {
+        let word = intern_word(intern, word);
+        biggest_word = biggest_word.max(word.len());
+        word_counts.entry(K::from_word(word)).or_insert(0);
+      }
+    }
+  }
+
+  if config.redact {
+    biggest_word = REDACTED_LEN;
+  }
+  let end = if config.print0 { '\0' } else { '\n' };
+  if config.trailing_whitespace {
+    let total: usize = trailing_whitespace.iter().map(|(_, lines)| lines).sum();
+    let width = total.to_string().len();
+    for (path, lines) in trailing_whitespace.iter() {
+      print!(
+        "{lines:>width$} {path}{end}",
+        lines = lines,
+        path = path.display(),
+        width = width,
+        end = end
+      );
+    }
+    print!(
+      "{total:>width$} lines with trailing whitespace in {files} files{end}",
+      total = total,
+      files = trailing_whitespace.len(),
+      width = width,
+      end = end
+    );
+    return Ok(());
+  }
+  if config.stats_per_file {
+    let width = |n: usize| n.to_string().len();
+    let (bw, cw, ww) = file_stats.iter().fold((5, 5, 5), |(bw, cw, ww), &(_, b, c, w)| {
+      (bw.max(width(b)), cw.max(width(c)), ww.max(width(w)))
+    });
+    print!(
+      "{:>bw$} {:>cw$} {:>ww$} path{end}",
+      "bytes",
+      "chars",
+      "words",
+      bw = bw,
+      cw = cw,
+      ww = ww,
+      end = end
+    );
+    for (path, bytes, chars, words) in file_stats.iter() {
+      print!(
+        "{bytes:>bw$} {chars:>cw$} {words:>ww$} {path}{end}",
+        bytes = bytes,
+        chars = chars,
+        words = words,
+        path = path.display(),
+        bw = bw,
+        cw = cw,
+        ww = ww,
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if config.by_mtime_year {
+    for (year, tokens) in year_tokens.iter() {
+      match year {
+        Some(year) => print!("{year:>7}: {tokens}{end}", year = year, tokens = tokens, end = end),
+        None => print!("unknown: {tokens}{end}", tokens = tokens, end = end),
+      }
+    }
+    return Ok(());
+  }
+  if let Some(symbol) = &config.around_symbol {
+    let sides = vec![("before", before_symbol), ("after", after_symbol)];
+    for (i, (side, counts)) in sides.into_iter().enumerate() {
+      if i > 0 {
+        print!("{end}", end = end);
+      }
+      print!("{side} `{symbol}`:{end}", side = side, symbol = symbol, end = end);
+      let mut v: Vec<(K, usize)> = counts.into_iter().collect();
+      sort_counts(config, &mut v);
+      let biggest_word = v.iter().map(|(key, _)| shown_word(config, key.word()).len()).max();
+      for (key, count) in v.iter() {
+        print!(
+          "{word:>biggest_word$}: {count}{end}",
+          word = shown_word(config, key.word()),
+          count = count,
+          biggest_word = biggest_word.unwrap_or(0),
+          end = end
+        );
+      }
+    }
+    return Ok(());
+  }
+  if config.acronyms {
+    let mut acronym_counts: Vec<(&str, usize)> = acronym_counts.into_iter().collect();
+    if config.print_by_frequency {
+      acronym_counts.sort_by(|(a1, c1), (a2, c2)| c2.cmp(c1).then_with(|| a1.cmp(a2)));
+    }
+    let biggest_acronym =
+      acronym_counts.iter().map(|(acronym, _)| shown_word(config, acronym).len()).max();
+    for (acronym, count) in acronym_counts.iter() {
+      print!(
+        "{acronym:>biggest_acronym$}: {count}{end}",
+        acronym = shown_word(config, acronym),
+        count = count,
+        biggest_acronym = biggest_acronym.unwrap_or(0),
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if config.count_emoji {
+    let mut emoji_counts: Vec<(&str, usize)> = emoji_counts.into_iter().collect();
+    if config.print_by_frequency {
+      emoji_counts.sort_by(|(e1, c1), (e2, c2)| c2.cmp(c1).then_with(|| e1.cmp(e2)));
+    }
+    for (emoji, count) in emoji_counts.iter() {
+      print!("{emoji}: {count}{end}", emoji = shown_word(config, emoji), count = count, end = end);
+    }
+    return Ok(());
+  }
+  if config.sentences {
+    sentence_lengths.sort_unstable();
+    let count = sentence_lengths.len();
+    let total: usize = sentence_lengths.iter().sum();
+    let median = match count {
+      0 => 0.0,
+      _ if count.is_multiple_of(2) => {
+        (sentence_lengths[count / 2 - 1] + sentence_lengths[count / 2]) as f64 / 2.0
+      }
+      _ => sentence_lengths[count / 2] as f64,
+    };
+    print!("sentences: {count}{end}", count = count, end = end);
+    print!("      min: {min}{end}", min = sentence_lengths.first().unwrap_or(&0), end = end);
+    print!("      max: {max}{end}", max = sentence_lengths.last().unwrap_or(&0), end = end);
+    print!(
+      "     mean: {mean:.1}{end}",
+      mean = if count == 0 { 0.0 } else { total as f64 / count as f64 },
+      end = end
+    );
+    print!("   median: {median:.1}{end}", median = median, end = end);
+    return Ok(());
+  }
+  if config.redundancy {
+    let estimate = word_model_bytes(word_counts.iter().map(|(key, &count)| (key.word(), count)));
+    let redundancy = if bytes_read == 0 { 0.0 } else { 1.0 - estimate / bytes_read as f64 };
+    print!(
+      "redundancy: {redundancy:.3} ({estimate:.0} estimated of {bytes} bytes read){end}",
+      redundancy = redundancy,
+      estimate = estimate,
+      bytes = bytes_read,
+      end = end
+    );
+    return Ok(());
+  }
+  if config.caps_ratio {
+    print!(
+      "{caps} of {total} words ({percent:.1}%) are all caps.{end}",
+      caps = caps_words,
+      total = total_words,
+      percent = if total_words == 0 { 0.0 } else { 100.0 * caps_words as f64 / total_words as f64 },
+      end = end
+    );
+    return Ok(());
+  }
+  if config.positions {
+    let mut v: Vec<(K, usize)> = word_counts.into_iter().collect();
+    sort_counts(config, &mut v);
+    for (key, _) in v.iter() {
+      let (position_sum, seen) = positions.get(key).copied().unwrap_or((0.0, 0));
+      print!(
+        "{word:>biggest_word$}: {position:.3}{end}",
+        word = shown_word(config, key.word()),
+        position = position_sum / seen.max(1) as f64,
+        biggest_word = biggest_word,
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if config.doubled_words {
+    for (path, word_number, [first, second]) in &doubled_words {
+      print!(
+        "{path}: word {word_number}: {first} {second}{end}",
+        path = path.display(),
+        word_number = word_number,
+        first = shown_word(config, first),
+        second = shown_word(config, second),
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if config.introduction_curve {
+    for (i, (unique_sum, files)) in curve.iter().enumerate() {
+      print!(
+        "{word_number}: {unique:.3}{end}",
+        word_number = i + 1,
+        unique = *unique_sum as f64 / *files as f64,
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if config.vocab_growth {
+    if config.print_by_frequency {
+      vocab_growth.sort_by(|(_, g1), (_, g2)| g2.cmp(g1));
+    }
+    let paths: Vec<String> = vocab_growth.iter().map(|(p, _)| p.display().to_string()).collect();
+    let biggest_path = paths.iter().map(String::len).max().unwrap_or(0);
+    for (path, (_, growth)) in paths.iter().zip(vocab_growth.iter()) {
+      print!(
+        "{path:>biggest_path$}: {growth}{end}",
+        path = path,
+        growth = growth,
+        biggest_path = biggest_path,
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if let Some(edges) = &config.buckets {
+    print_buckets(word_counts.values().copied(), edges, true, end);
+    return Ok(());
+  }
+  if config.log_buckets {
+    let edges = log_bucket_edges(word_counts.values().copied().max().unwrap_or(0));
+    print_buckets(word_counts.values().copied(), &edges, false, end);
+    return Ok(());
+  }
+  if let Some(baseline) = &config.ratio_to {
+    let mut base_counts: HashMap<K, usize> = HashMap::new();
+    for (word, count) in baseline.iter() {
+      let word = intern_word(intern, word);
+      biggest_word = biggest_word.max(word.len());
+      *base_counts.entry(K::from_word(word)).or_insert(0) += count;
+    }
+    let total: usize = word_counts.values().sum();
+    let base_total: usize = base_counts.values().sum();
+    // each word's share of this run's words over its share of the baseline's,
+    // with words that are new here at infinity and words that are gone at 0.
+    let mut ratios: Vec<(K, f64)> = word_counts
+      .iter()
+      .map(|(&key, &count)| match base_counts.remove(&key) {
+        Some(base_count) if base_count > 0 => {
+          (key, (count as f64 / total as f64) / (base_count as f64 / base_total as f64))
+        }
+        _ => (key, f64::INFINITY),
+      })
+      .collect();
+    ratios.extend(base_counts.into_keys().map(|key| (key, 0.0)));
+    ratios.sort_unstable_by(|(w1, r1), (w2, r2)| {
+      r2.partial_cmp(r1).unwrap_or(Ordering::Equal).then_with(|| w1.word().cmp(w2.word()))
+    });
+    for (key, ratio) in ratios.iter() {
+      let ratio = if ratio.is_infinite() {
+        "new".to_string()
+      } else if *ratio == 0.0 {
+        "gone".to_string()
+      } else {
+        format!("{ratio:.3}", ratio = ratio)
+      };
+      print!(
+        "{word:>biggest_word$}: {ratio}{end}",
+        word = shown_word(config, key.word()),
+        ratio = ratio,
+        biggest_word = biggest_word,
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if let Some(pct) = config.coverage {
+    let total: usize = word_counts.values().sum();
+    let mut v: Vec<(K, usize)> = word_counts.into_iter().collect();
+    v.sort_unstable_by(|(w1, c1), (w2, c2)| c2.cmp(c1).then_with(|| w1.word().cmp(w2.word())));
+    let mut covered: usize = 0;
+    let mut needed: usize = 0;
+    for (key, count) in v.iter() {
+      if covered as f64 >= total as f64 * pct / 100.0 {
+        break;
+      }
+      covered += count;
+      needed += 1;
+      if config.words_only {
+        print!("{word}{end}", word = shown_word(config, key.word()), end = end);
+        continue;
+      }
+      print!(
+        "{word:>biggest_word$}: {count}{end}",
+        word = shown_word(config, key.word()),
+        count = count,
+        biggest_word = biggest_word,
+        end = end
+      );
+    }
+    print!(
+      "{needed} words cover {percent:.1}% of the {total} words.{end}",
+      needed = needed,
+      percent = if total == 0 { 0.0 } else { 100.0 * covered as f64 / total as f64 },
+      total = total,
+      end = end
+    );
+    return Ok(());
+  }
+  if config.flag_mixed_script {
+    for (key, count) in word_counts.iter() {
+      let scripts = scripts_of(key.word());
+      if scripts.len() > 1 {
+        print!(
+          "{word:>biggest_word$}: {scripts} (seen {count}){end}",
+          word = shown_word(config, key.word()),
+          scripts = scripts.join(" + "),
+          count = count,
+          biggest_word = biggest_word,
+          end = end
+        );
+      }
+    }
+    return Ok(());
+  }
+  if config.by_category {
+    let mut categories: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for (key, count) in word_counts.iter() {
+      *categories.entry(category_of(key.word())).or_insert(0) += count;
+    }
+    let mut categories: Vec<(&str, usize)> = categories.into_iter().collect();
+    if config.print_by_frequency {
+      categories.sort_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then_with(|| n1.cmp(n2)));
+    }
+    let biggest_name = categories.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, count) in categories.iter() {
+      print!(
+        "{name:>biggest_name$}: {count}{end}",
+        name = name,
+        count = count,
+        biggest_name = biggest_name,
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if !config.sources.is_empty() {
+    print_source_table(config, group_counts, biggest_word, end);
+    return Ok(());
+  }
+  if config.by_directory || config.by_language {
+    for (i, (group, counts)) in group_counts.into_iter().enumerate() {
+      if i > 0 {
+        print!("{end}", end = end);
+      }
+      print!("{group}:{end}", group = group, end = end);
+      let v: Vec<(K, usize)> = match config.heap_top {
+        Some(n) => heap_top(counts.into_iter(), n),
+        None => {
+          let mut v: Vec<(K, usize)> = counts.into_iter().collect();
+          sort_counts(config, &mut v);
+          v
+        }
+      };
+      for (key, count) in v.iter() {
+        if config.words_only {
+          print!("{word}{end}", word = shown_word(config, key.word()), end = end);
+          continue;
+        }
+        print!(
+          "{word:>biggest_word$}: {count}{end}",
+          word = shown_word(config, key.word()),
+          count = count,
+          biggest_word = biggest_word,
+          end = end
+        );
+      }
+    }
+    return Ok(());
+  }
+
+  let meta = ReportMeta {
+    files: files_read,
+    tokens: word_counts.values().sum(),
+    unique_words: word_counts.len(),
+    written: SystemTime::now(),
+  };
+  let mut v: Vec<(K, usize)> = match config.heap_top {
+    Some(n) => heap_top(word_counts.into_iter(), n),
+    None => word_counts.into_iter().collect(),
+  };
+  if let Some(path) = &config.also_json {
+    let rows: Vec<(Cow<str>, usize)> =
+      v.iter().map(|&(key, count)| (shown_word(config, key.word()), count)).collect();
+    if let Err(e) = write_json(path, rows.iter().map(|(word, count)| (&**word, *count))) {
+      eprintln!("Couldn't write JSON to {path}: {e}", path = path, e = e);
+    }
+  }
+  if let Some(path) = &config.report_json {
+    let rows: Vec<(Cow<str>, usize)> =
+      v.iter().map(|&(key, count)| (shown_word(config, key.word()), count)).collect();
+    let written =
+      write_report_json(path, &meta, rows.iter().map(|(word, count)| (&**word, *count)));
+    if let Err(e) = written {
+      eprintln!("Couldn't write the JSON report to {path}: {e}", path = path, e = e);
+    }
+  }
+  if let Some(path) = &config.wordcloud {
+    let top = heap_top(v.iter().copied(), config.heap_top.unwrap_or(WORDCLOUD_WORDS));
+    let words: Vec<(Cow<str>, usize)> =
+      top.iter().map(|&(key, count)| (shown_word(config, key.word()), count)).collect();
+    let words: Vec<(&str, usize)> = words.iter().map(|(word, count)| (&**word, *count)).collect();
+    if let Err(e) = wordcloud::write_svg(path, &words) {
+      eprintln!("Couldn't write the word cloud to {path}: {e}", path = path, e = e);
+    }
+  }
+  #[cfg(feature = "tui")]
+  if config.tui {
+    if std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+      let rows = alphabetical_rows(config, &v, intern);
+      if let Err(e) = tui::browse(rows, config.print_by_frequency) {
+        eprintln!("Error in the TUI: {e}", e = e);
+      }
+      return Ok(());
+    }
+    eprintln!("--tui needs a terminal to run in, printing normally instead.");
+  }
+  if config.interactive {
+    let rows = alphabetical_rows(config, &v, intern);
+    if let Err(e) = repl::explore(rows, config.print_by_frequency, config.case_sensitive) {
+      eprintln!("Error in --interactive: {e}", e = e);
+    }
+    return Ok(());
+  }
+  if config.weights.is_some() || config.time_decay.is_some() {
+    let mut weighted: Vec<(K, f64)> = weighted_counts.into_iter().collect();
+    if config.print_by_frequency {
+      weighted.sort_unstable_by(|(w1, c1), (w2, c2)| {
+        c2.partial_cmp(c1).unwrap_or(Ordering::Equal).then_with(|| w1.word().cmp(w2.word()))
+      });
+    }
+    for (key, weight) in weighted.iter() {
+      if config.words_only {
+        print!("{word}{end}", word = shown_word(config, key.word()), end = end);
+        continue;
+      }
+      print!(
+        "{word:>biggest_word$}: {weight}{end}",
+        word = shown_word(config, key.word()),
+        weight = weight,
+        biggest_word = biggest_word,
+        end = end
+      );
+    }
+    return Ok(());
+  }
+  if config.heap_top.is_some() {
+    // already in frequency order.
+  } else if let Some(reference) = &config.order_by {
+    let counts: HashMap<K, usize> = v.drain(..).collect();
+    let mut seen: HashSet<K> = HashSet::new();
+    for (word, _) in reference.iter() {
+      let word = intern_word(intern, word);
+      let key = K::from_word(word);
+      if seen.insert(key) {
+        biggest_word = biggest_word.max(word.len());
+        v.push((key, counts.get(&key).copied().unwrap_or(0)));
+      }
+    }
+    let mut unreferenced: Vec<(K, usize)> =
+      counts.into_iter().filter(|(key, _)| !seen.contains(key)).collect();
+    unreferenced.sort_unstable_by(|(w1, c1), (w2, c2)| c2.cmp(c1).then_with(|| w1.cmp(w2)));
+    v.extend(unreferenced);
+  } else {
+    sort_counts(config, &mut v);
+  }
+  // Note(Lokathor): `v` only holds the keys and counts, each line is formatted
+  // straight into the buffer, so the whole output is never in memory at once.
+  let mut out = counts_output(config);
+  if config.prometheus {
+    let header = write!(
+      out,
+      "# HELP whist_word_count How many times each word was used.\n\
+       # TYPE whist_word_count gauge\n"
+    );
+    if let Err(e) = header {
+      write_failed(&mut out, e);
+      return Ok(());
+    }
+  }
+  for (i, (key, count)) in v.iter().enumerate() {
+    let written = if config.prometheus {
+      writeln!(
+        out,
+        "whist_word_count{{word=\"{word}\"}} {count}",
+        word = prometheus_label(&shown_word(config, key.word())),
+        count = count
+      )
+    } else if config.words_only {
+      write!(out, "{word}{end}", word = shown_word(config, key.word()), end = end)
+    } else if config.with_doc_count {
+      write!(
+        out,
+        "{word:>biggest_word$}: {count} {files}{end}",
+        word = shown_word(config, key.word()),
+        count = count,
+        files = doc_counts.get(key).map(|&(_, files)| files).unwrap_or(0),
+        biggest_word = biggest_word,
+        end = end
+      )
+    } else {
+      write!(
+        out,
+        "{word:>biggest_word$}: {count}{end}",
+        word = shown_word(config, key.word()),
+        count = count,
+        biggest_word = biggest_word,
+        end = end
+      )
+    };
+    let flushed = match config.flush_every {
+      Some(n) if (i + 1).is_multiple_of(n) => out.flush(),
+      _ => Ok(()),
+    };
+    if let Err(e) = written.and(flushed) {
+      write_failed(&mut out, e);
+      return Ok(());
+    }
+  }
+  if config.extract_urls.is_some() {
+    for (heading, counts) in [("urls", url_counts), ("paths", path_counts)] {
+      if heading == "paths" && counts.is_empty() {
+        continue;
+      }
+      let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+      if config.print_by_frequency {
+        counts.sort_by(|(u1, c1), (u2, c2)| c2.cmp(c1).then_with(|| u1.cmp(u2)));
+      }
+      if let Err(e) = write!(out, "{end}{heading}:{end}", heading = heading, end = end) {
+        write_failed(&mut out, e);
+        return Ok(());
+      }
+      for (url, count) in counts.iter() {
+        let url = shown_word(config, url);
+        if let Err(e) = write!(out, "{url}: {count}{end}", url = url, count = count, end = end) {
+          write_failed(&mut out, e);
+          return Ok(());
+        }
+      }
+    }
+  }
+  let _ = out.flush();
+  Ok(())
+}
+
+/// Where the list of counts gets written: stdout, encoded with
+/// `--output-encoding` if that's given.
+fn counts_output(config: &Config) -> Box<dyn Write> {
+  // the tests look at the counts through `--also-json` instead.
+  if cfg!(test) {
+    return Box::new(std::io::sink());
+  }
+  let out = BufWriter::new(std::io::stdout().lock());
+  match config.output_encoding {
+    Some((encoding, error_on_unmappable)) => {
+      Box::new(EncodingWriter { out, encoding, error_on_unmappable })
+    }
+    None => Box::new(out),
+  }
+}
+
+/// Says why writing the counts stopped, if it's worth saying.
+fn write_failed(out: &mut dyn Write, e: std::io::Error) {
+  // the reader going away (such as `head` being done) just means nobody wants
+  // the rest of the words, but a char that `--output-encoding` can't write
+  // with `--unmappable error` is a real error.
+  if e.kind() == std::io::ErrorKind::InvalidData {
+    // the counts before the bad one still go out.
+    let _ = out.flush();
+    eprintln!("Couldn't write the counts: {e}", e = e);
+    std::process::exit(1);
+  }
+}
+
+/// Encodes the UTF-8 written to it into another encoding, for
+/// `--output-encoding`.
+///
+/// Each write must be whole chars. Every write gets a fresh encoder that's
+/// finished at the end of it, so stateful encodings (like ISO-2022-JP) are
+/// always back in their starting state between writes.
+struct EncodingWriter<W> {
+  out: W,
+  encoding: &'static Encoding,
+  error_on_unmappable: bool,
+}
+
+impl<W: Write> EncodingWriter<W> {
+  fn encode(&mut self, encoder: &mut Encoder, mut text: &str, last: bool) -> std::io::Result<()> {
+    let mut buf = [0; 1024];
+    loop {
+      let (result, read, written) =
+        encoder.encode_from_utf8_without_replacement(text, &mut buf, last);
+      self.out.write_all(&buf[..written])?;
+      text = &text[read..];
+      match result {
+        EncoderResult::InputEmpty => return Ok(()),
+        EncoderResult::OutputFull => (),
+        EncoderResult::Unmappable(c) if self.error_on_unmappable => {
+          let message =
+            format!("`{c}` can't be written in {name}", c = c, name = self.encoding.name());
+          return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+        }
+        EncoderResult::Unmappable(_) => self.encode(encoder, "?", false)?,
+      }
+    }
+  }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+  fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+    let text = std::str::from_utf8(bytes)
+      .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let mut encoder = self.encoding.new_encoder();
+    self.encode(&mut encoder, text, true)?;
+    Ok(bytes.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.out.flush()
+  }
+}
+
+/// The words and counts as they should be shown, in alphabetical order, for
+/// the `--tui` and `--interactive` views.
+///
+/// The counts might not be in alphabetical order already, since `--heap-top`
+/// puts them in frequency order.
+fn alphabetical_rows<K: WordKey>(
+  config: &Config, counts: &[(K, usize)], intern: &mut HashSet<&'static str>,
+) -> Vec<(&'static str, usize)> {
+  let mut counts = counts.to_vec();
+  counts.sort_unstable_by_key(|&(key, _)| key);
+  counts
+    .into_iter()
+    .map(|(key, count)| match shown_word(config, key.word()) {
+      Cow::Borrowed(word) => (word, count),
+      Cow::Owned(hash) => (intern_word(intern, &hash), count),
+    })
+    .collect()
+}
+
+/// Prints the `--source` counts as a table, with a row for each word and a
+/// column for each label.
+fn print_source_table<K: WordKey>(
+  config: &Config, label_counts: BTreeMap<String, BTreeMap<K, usize>>, biggest_word: usize,
+  end: char,
+) {
+  // the columns go in the order the labels were first given.
+  let mut labels: Vec<&str> = Vec::new();
+  for (label, _) in config.sources.iter() {
+    if !labels.contains(&label.as_str()) {
+      labels.push(label);
+    }
+  }
+  let mut rows: BTreeMap<K, Vec<usize>> = BTreeMap::new();
+  for (column, label) in labels.iter().enumerate() {
+    for (&key, &count) in label_counts.get(*label).into_iter().flatten() {
+      rows.entry(key).or_insert_with(|| vec![0; labels.len()])[column] = count;
+    }
+  }
+  let mut rows: Vec<(K, Vec<usize>)> = rows.into_iter().collect();
+  if config.print_by_frequency {
+    rows.sort_by_cached_key(|(key, counts)| (Reverse(counts.iter().sum::<usize>()), key.word()));
+  }
+  let widths: Vec<usize> = labels
+    .iter()
+    .enumerate()
+    .map(|(column, label)| {
+      let biggest_count = rows.iter().map(|(_, counts)| counts[column]).max().unwrap_or(0);
+      label.chars().count().max(biggest_count.to_string().len())
+    })
+    .collect();
+  let mut line = format!("{blank:>biggest_word$} ", blank = "", biggest_word = biggest_word);
+  for (label, width) in labels.iter().zip(widths.iter()) {
+    line.push_str(&format!(" {label:>width$}", label = label, width = width));
+  }
+  print!("{line}{end}", line = line, end = end);
+  for (key, counts) in rows.iter() {
+    let mut line = format!(
+      "{word:>biggest_word$}:",
+      word = shown_word(config, key.word()),
+      biggest_word = biggest_word
+    );
+    for (count, width) in counts.iter().zip(widths.iter()) {
+      line.push_str(&format!(" {count:>width$}", count = count, width = width));
+    }
+    print!("{line}{end}", line = line, end = end);
+  }
+}
+
+/// Sorts counts for printing by `--sort-by-length` or `--print-by-frequency`,
+/// and otherwise leaves them alphabetical (by `--collate` if it was given).
+fn sort_counts<K: WordKey>(config: &Config, v: &mut [(K, usize)]) {
+  if let Some(order) = config.sort_by_length {
+    v.sort_unstable_by(|(w1, c1), (w2, c2)| {
+      let (l1, l2) = (w1.word().chars().count(), w2.word().chars().count());
+      let by_length = match order {
+        LengthOrder::Longest => l2.cmp(&l1),
+        LengthOrder::Shortest => l1.cmp(&l2),
+      };
+      by_length.then_with(|| c2.cmp(c1)).then_with(|| w1.word().cmp(w2.word()))
+    });
+  } else if config.print_by_frequency {
+    v.sort_unstable_by(|(w1, c1), (w2, c2)| match c1.cmp(c2) {
+      Ordering::Less => Ordering::Greater,
+      Ordering::Greater => Ordering::Less,
+      Ordering::Equal => w1.word().cmp(w2.word()),
+    });
+  } else if let Some(collator) = &config.collator {
+    v.sort_by(|(w1, _), (w2, _)| collator.compare(w1.word(), w2.word()));
+  }
 }
 
-fn main() {
-  let args: Vec<String> = std::env::args().collect();
-  if args.iter().any(|s| s.as_str() == "--help") {
-    print_help();
-    return;
+/// The names of the scripts that the letters of a word are written in, in the
+/// order they first show up.
+///
+/// The `Common` and `Inherited` scripts, which are things like digits and
+/// combining marks that go with any script, are left out.
+fn scripts_of(word: &str) -> Vec<&'static str> {
+  let mut scripts: Vec<&'static str> = Vec::new();
+  for c in word.chars() {
+    let script = c.script();
+    if script == Script::Common || script == Script::Inherited {
+      continue;
+    }
+    if !scripts.contains(&script.full_name()) {
+      scripts.push(script.full_name());
+    }
   }
-  let print_by_frequency =
-    if args.iter().any(|s| s.as_str() == "--print-by-frequency") { true } else { false };
-  let case_sensitive =
-    if args.iter().any(|s| s.as_str() == "--case-sensitive") { true } else { false };
+  scripts
+}
 
-  const TEN_MEGABYTES: usize = 10 * 1024 * 1024;
-  let mut buf = Vec::with_capacity(TEN_MEGABYTES);
-  let mut intern: HashSet<&'static str> = HashSet::new();
-  let mut word_counts: BTreeMap<UniCase<&'static str>, usize> = BTreeMap::new();
-  let mut word_counts_cased: BTreeMap<&'static str, usize> = BTreeMap::new();
-  let mut biggest_word: usize = 0;
+/// The long name of the Unicode general category that most of the chars of a
+/// word are in, with a tie going to the category seen first.
+fn category_of(word: &str) -> &'static str {
+  let categories = CodePointMapData::<GeneralCategory>::new();
+  let mut tallies: Vec<(GeneralCategory, usize)> = Vec::new();
+  for c in word.chars() {
+    let category = categories.get(c);
+    match tallies.iter_mut().find(|(seen, _)| *seen == category) {
+      Some((_, tally)) => *tally += 1,
+      None => tallies.push((category, 1)),
+    }
+  }
+  // `max_by_key` gives the last of equal maximums, so go in reverse.
+  let most = tallies.iter().rev().max_by_key(|(_, tally)| *tally).map(|&(category, _)| category);
+  most
+    .and_then(|category| PropertyNamesLong::<GeneralCategory>::new().get(category))
+    .unwrap_or("Unassigned")
+}
 
-  recursive_read_dir(".", |p| {
-    match std::fs::File::open(&p) {
-      Err(e) => eprintln!("Couldn't open {path}: {e}", path = p.display(), e = e),
-      Ok(mut f) => match std::io::Read::read_to_end(&mut f, &mut buf) {
-        Err(e) => eprintln!("Error while reading {path}: {e}", path = p.display(), e = e),
-        Ok(_byte_count_read) => {
-          for word in buf.words() {
-            let interned_letters: &'static str = intern.get(word).copied().unwrap_or_else(|| {
-              let leaked: &'static str = Box::leak(String::from(word).into_boxed_str());
-              intern.insert(leaked);
-              biggest_word = biggest_word.max(leaked.len());
-              leaked
-            });
-            if case_sensitive {
-              *word_counts_cased.entry(interned_letters).or_insert(0) += 1;
-            } else {
-              *word_counts.entry(UniCase::new(interned_letters)).or_insert(0) += 1;
-            }
-          }
-        }
-      },
-    }
-    buf.clear();
-  });
+/// How many of the top words `--wordcloud` shows, unless `--heap-top` says.
+const WORDCLOUD_WORDS: usize = 100;
+
+/// How many of the top words `--prometheus` prints, unless `--heap-top` says.
+const PROMETHEUS_WORDS: usize = 100;
+
+/// Escapes a label value for the Prometheus text format, where `\`, `"`, and
+/// newlines have to be backslash escaped.
+fn prometheus_label(value: &str) -> Cow<'_, str> {
+  if !value.contains(['\\', '"', '\n']) {
+    return Cow::Borrowed(value);
+  }
+  let mut escaped = String::with_capacity(value.len() + 2);
+  for c in value.chars() {
+    match c {
+      '\\' => escaped.push_str("\\\\"),
+      '"' => escaped.push_str("\\\""),
+      '\n' => escaped.push_str("\\n"),
+      c => escaped.push(c),
+    }
+  }
+  Cow::Owned(escaped)
+}
+
+/// Picks out the `n` highest counts, most frequent first, with a heap that never
+/// holds more than `n + 1` of them.
+fn heap_top<K: WordKey>(counts: impl Iterator<Item = (K, usize)>, n: usize) -> Vec<(K, usize)> {
+  let mut heap = BinaryHeap::with_capacity(n + 1);
+  for (key, count) in counts {
+    // the heap's top is the row that's "least" worth keeping: the lowest
+    // count, and then the last word alphabetically.
+    heap.push(Reverse((count, Reverse(key.word()), key)));
+    if heap.len() > n {
+      heap.pop();
+    }
+  }
+  heap.into_sorted_vec().into_iter().map(|Reverse((count, _, key))| (key, count)).collect()
+}
+
+/// The directory `--by-directory` puts a file under: the first part of the
+/// path below the root, or `.` for files right in the root.
+fn top_directory(root: &Path, path: &Path) -> String {
+  let path = path.strip_prefix(root).unwrap_or(path);
+  let mut components = path.components();
+  match (components.next(), components.next()) {
+    (Some(first), Some(_)) => first.as_os_str().to_string_lossy().into_owned(),
+    _ => ".".to_string(),
+  }
+}
+
+/// The ISO 639-3 code of the language `--by-language` thinks the text is in,
+/// or `unknown` if the guess isn't a confident one.
+fn language_of(text: &str) -> &'static str {
+  match whatlang::detect(text) {
+    Some(info) if info.is_reliable() => info.lang().code(),
+    _ => "unknown",
+  }
+}
+
+/// The device and inode numbers of a file (following symlinks), which are the
+/// same for every hard link or symlink to that file.
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+  use std::os::unix::fs::MetadataExt;
+  std::fs::metadata(path).ok().map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+/// Without inodes to go by, files can't be told apart this way.
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+  None
+}
+
+/// Writes the counts into the `counts` table of a SQLite database, making the
+/// table if it's not there yet.
+///
+/// The table's old rows are all replaced, unless `add` is set, in which case
+/// the new counts are added on to the old ones.
+///
+/// This goes through `rusqlite`, with a copy of SQLite built in to whist (its
+/// `bundled` feature), so the system doesn't need SQLite installed.
+#[cfg(feature = "sqlite")]
+fn write_sqlite<S: AsRef<str>>(
+  path: &str, rows: impl Iterator<Item = (S, usize)>, add: bool,
+) -> rusqlite::Result<()> {
+  let mut db = rusqlite::Connection::open(path)?;
+  let tx = db.transaction()?;
+  tx.execute("CREATE TABLE IF NOT EXISTS counts (word TEXT PRIMARY KEY, count INTEGER)", ())?;
+  if !add {
+    tx.execute("DELETE FROM counts", ())?;
+  }
+  {
+    let mut insert = tx.prepare(
+      "INSERT INTO counts (word, count) VALUES (?1, ?2) \
+      ON CONFLICT(word) DO UPDATE SET count = count + excluded.count",
+    )?;
+    for (word, count) in rows {
+      insert.execute((word.as_ref(), count as i64))?;
+    }
+  }
+  tx.commit()
+}
+
+/// The first bytes of every `--checkpoint` file.
+const CHECKPOINT_MAGIC: &[u8; 8] = b"WHISTCP1";
+
+/// Saves a `--checkpoint` the same way as [`save_binary`], so it's never cut
+/// off.
+///
+/// The format is [`CHECKPOINT_MAGIC`], then the number of files counted as a
+/// little-endian `u64`, then each file's path as a little-endian `u32` length
+/// and UTF-8 (lossily), and then the counts in the [`binary`] format.
+fn save_checkpoint<K: WordKey>(
+  path: &str, files: &HashSet<String>, word_counts: &BTreeMap<K, usize>,
+) -> std::io::Result<()> {
+  let mut rows: Vec<(&str, u64)> =
+    word_counts.iter().map(|(key, &count)| (key.word(), count as u64)).collect();
+  rows.sort_unstable_by_key(|&(word, _)| word);
+  let temp = format!("{path}.tmp", path = path);
+  let f = std::fs::File::create(&temp)?;
+  let mut out = BufWriter::new(&f);
+  out.write_all(CHECKPOINT_MAGIC)?;
+  out.write_all(&(files.len() as u64).to_le_bytes())?;
+  for file in files {
+    out.write_all(&(file.len() as u32).to_le_bytes())?;
+    out.write_all(file.as_bytes())?;
+  }
+  binary::write(&mut out, rows)?;
+  out.flush()?;
+  drop(out);
+  f.sync_all()?;
+  std::fs::rename(&temp, path)
+}
+
+/// What a `--checkpoint` file holds.
+struct Checkpoint {
+  /// The paths of the files already counted.
+  files: HashSet<String>,
+  counts: Vec<(String, u64)>,
+}
+
+/// Loads a [`save_checkpoint`] file.
+fn load_checkpoint(path: &str) -> std::io::Result<Checkpoint> {
+  let bad_data = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+  let mut input = std::io::BufReader::new(std::fs::File::open(path)?);
+  let mut magic = [0; 8];
+  input.read_exact(&mut magic)?;
+  if &magic != CHECKPOINT_MAGIC {
+    return Err(bad_data("not a whist checkpoint file"));
+  }
+  let mut len = [0; 8];
+  input.read_exact(&mut len)?;
+  let mut files = HashSet::new();
+  for _ in 0..u64::from_le_bytes(len) {
+    let mut len = [0; 4];
+    input.read_exact(&mut len)?;
+    let mut file = vec![0; u32::from_le_bytes(len) as usize];
+    input.read_exact(&mut file)?;
+    files.insert(String::from_utf8(file).map_err(|_| bad_data("a path isn't UTF-8"))?);
+  }
+  let counts = binary::read(input).collect::<std::io::Result<_>>()?;
+  Ok(Checkpoint { files, counts })
+}
+
+/// If a path is one of the files that whist writes its results to (or a
+/// temporary file made while writing one), which shouldn't be counted as text
+/// when they're in the directory being walked.
+///
+/// The directories get compared instead of the files themselves, so this also
+/// works for a file that was just removed.
+fn is_output_file(config: &Config, path: &Path) -> bool {
+  let checkpoint = config.checkpoint.iter().map(|(path, _)| path);
+  let outputs = config
+    .save_binary
+    .iter()
+    .chain(config.update.iter())
+    .chain(checkpoint)
+    .chain(config.also_json.iter())
+    .chain(config.report_json.iter())
+    .chain(config.wordcloud.iter())
+    .chain(config.sqlite.iter())
+    .chain(config.reverse_index.iter());
+  let dir_of = |path: &Path| match path.parent() {
+    Some(parent) if parent.as_os_str().is_empty() => Path::new(".").canonicalize().ok(),
+    Some(parent) => parent.canonicalize().ok(),
+    None => None,
+  };
+  let name = match path.file_name() {
+    Some(name) => name.to_string_lossy(),
+    None => return false,
+  };
+  outputs.map(Path::new).any(|output| {
+    let output_name = match output.file_name() {
+      Some(output_name) => output_name.to_string_lossy(),
+      None => return false,
+    };
+    let same_name = name == output_name
+      || name.strip_prefix(&*output_name).is_some_and(|rest| rest == ".tmp" || rest == "-journal");
+    same_name && dir_of(output).is_some_and(|dir| dir_of(path) == Some(dir))
+  })
+}
+
+/// How many hex digits of a word's hash `--redact` shows.
+const REDACTED_LEN: usize = 8;
 
-  if print_by_frequency {
-    use std::cmp::Ordering;
-    let mut v: Vec<(&'static str, usize)> = if case_sensitive {
-      word_counts_cased.into_iter().collect()
+/// The word as it should be printed: the word itself, or with `--redact` the
+/// start of its SHA-256 hash in hex.
+///
+/// When counting case insensitively the hash is of the case folded word (the
+/// same folding that `UniCase` compares with), since which case of a word gets
+/// printed depends on which was seen first.
+///
+/// Since this only happens when printing, two different words whose hashes
+/// start the same (about a 1 in 4 billion chance for each pair) still print
+/// as two lines.
+fn shown_word<'w>(config: &Config, word: &'w str) -> Cow<'w, str> {
+  if config.redact {
+    let hash = if config.case_sensitive || config.shapes {
+      Sha256::digest(word.as_bytes())
     } else {
-      word_counts.into_iter().map(|(uc, c)| (uc.into_inner(), c)).collect()
+      Sha256::digest(UniCase::new(word).to_folded_case().as_bytes())
     };
-    v.sort_unstable_by(|(w1, c1), (w2, c2)| match c1.cmp(c2) {
-      Ordering::Less => Ordering::Greater,
-      Ordering::Greater => Ordering::Less,
-      Ordering::Equal => w1.cmp(w2),
-    });
-    for (word, count) in v.iter() {
-      println!(
-        "{word:>biggest_word$}: {count}",
-        word = word,
-        count = count,
-        biggest_word = biggest_word
+    let hex: String = hash.iter().map(|byte| format!("{byte:02x}", byte = byte)).collect();
+    Cow::Owned(hex[..REDACTED_LEN].to_string())
+  } else {
+    Cow::Borrowed(word)
+  }
+}
+
+/// Saves binary counts to a temporary file next to `path`, then renames it
+/// over `path`, so that a crash part way through can't leave `path` cut off.
+fn save_binary(path: &str, rows: &[(&str, u64)]) -> std::io::Result<()> {
+  let temp = format!("{path}.tmp", path = path);
+  let f = std::fs::File::create(&temp)?;
+  binary::write(&f, rows.iter().copied())?;
+  f.sync_all()?;
+  std::fs::rename(&temp, path)
+}
+
+/// If a word has at least one uppercase letter and no lowercase letters.
+fn is_all_caps(word: &str) -> bool {
+  word.chars().any(char::is_uppercase) && !word.chars().any(char::is_lowercase)
+}
+
+/// How many bytes `--shebang` reads to find the first line of a file.
+const SHEBANG_PEEK_BYTES: u64 = 256;
+
+/// The first line of a file, if it starts with `#!`.
+///
+/// Only the first [`SHEBANG_PEEK_BYTES`] of the file are read.
+fn read_shebang(path: &Path) -> std::io::Result<Option<String>> {
+  let mut start = Vec::new();
+  std::fs::File::open(path)?.take(SHEBANG_PEEK_BYTES).read_to_end(&mut start)?;
+  if !start.starts_with(b"#!") {
+    return Ok(None);
+  }
+  let line = start.lines().next().unwrap_or_default();
+  Ok(Some(line.to_str_lossy().into_owned()))
+}
+
+/// The name of the program a shebang line runs, looking past `env`, so both
+/// `#!/bin/sh` and `#!/usr/bin/env -S python3 -u` give the program's name.
+fn shebang_interpreter(shebang: &str) -> &str {
+  let mut parts = shebang.trim_start_matches("#!").split_whitespace();
+  let program = parts.next().unwrap_or("");
+  let name = program.rsplit('/').next().unwrap_or(program);
+  if name == "env" {
+    parts.find(|part| !part.starts_with('-')).unwrap_or("")
+  } else {
+    name
+  }
+}
+
+/// What `--extract-urls` counts as a URL: a scheme, then `://`, then anything
+/// up to a space, a quote, or `<>`.
+const URL_PATTERN: &str = r#"[A-Za-z][A-Za-z0-9+.-]*://[^\s<>"'`]+"#;
+
+/// What `--extract-paths` counts as a path: an absolute (or `~/`, `./`,
+/// `../`) unix path, a windows path with a drive, or a relative path with a
+/// `/` in it whose last part has an extension.
+///
+/// The last rule keeps things like `and/or` and `2024/01/02` from counting.
+const PATH_PATTERN: &str = concat!(
+  r"\B(?:~|\.{1,2})?/[\w.-]+(?:/[\w.-]+)*/?",
+  r"|\b[A-Za-z]:\\[\w.-]+(?:\\[\w.-]+)*",
+  r"|\b[\w.-]+(?:/[\w.-]+)*/[\w-]+\.[A-Za-z][A-Za-z0-9]*\b",
+);
+
+/// Takes everything that `pattern` matches out of the text, giving back the
+/// text that's left and each of the matches.
+///
+/// Punctuation at the end of a match, such as the period ending a sentence,
+/// is left in the text instead. So is a `)` at the end that doesn't close a
+/// `(` in the match, so a URL in parentheses doesn't keep the `)`.
+fn extract_urls<'t>(text: &'t str, pattern: &Regex) -> (String, Vec<&'t str>) {
+  let mut rest = String::with_capacity(text.len());
+  let mut found = Vec::new();
+  let mut last_end = 0;
+  for m in pattern.find_iter(text) {
+    let mut url = m.as_str();
+    loop {
+      let unclosed = url.ends_with(')') && url.matches('(').count() < url.matches(')').count();
+      if url.ends_with(['.', ',', ';', ':', '!', '?']) || unclosed {
+        url = &url[..url.len() - 1];
+      } else {
+        break;
+      }
+    }
+    if url.is_empty() {
+      continue;
+    }
+    rest.push_str(&text[last_end..m.start()]);
+    rest.push(' ');
+    last_end = m.start() + url.len();
+    found.push(url);
+  }
+  rest.push_str(&text[last_end..]);
+  (rest, found)
+}
+
+/// The words of `text` that come right before a `symbol`, and the words that
+/// come right after one, going by the non-word chars between each two words.
+///
+/// The words are split the same way as the ones that get counted, except that
+/// `--phrases` aren't joined.
+fn words_around<'t>(
+  config: &'t Config, text: &'t str, symbol: &str,
+) -> (Vec<&'t str>, Vec<&'t str>) {
+  let mut before = Vec::new();
+  let mut after = Vec::new();
+  let mut last_word: Option<&str> = None;
+  let mut last_end = 0;
+  for word in split_tokens(config, text) {
+    // `split_tokens` only gives back slices of `text`, so this is where it starts.
+    let start = word.as_ptr() as usize - text.as_ptr() as usize;
+    if text[last_end..start].contains(symbol) {
+      before.extend(last_word);
+      after.push(word);
+    }
+    last_word = Some(word);
+    last_end = start + word.len();
+  }
+  if text[last_end..].contains(symbol) {
+    before.extend(last_word);
+  }
+  (before, after)
+}
+
+/// The shortest and longest words `--acronyms` counts.
+const ACRONYM_LENGTHS: std::ops::RangeInclusive<usize> = 2..=5;
+
+/// If a word is all uppercase letters, with a length in [`ACRONYM_LENGTHS`].
+fn is_acronym(word: &str) -> bool {
+  ACRONYM_LENGTHS.contains(&word.chars().count()) && word.chars().all(char::is_uppercase)
+}
+
+/// The longest line `--line-filter` looks at in one go.
+///
+/// A file with no newlines at all (minified JS, single line JSON, etc) would
+/// otherwise be a single line that's either entirely kept or entirely dropped.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// Cuts a line into pieces of at most `MAX_LINE_BYTES`, at whitespace when
+/// there's some, so that words don't get cut in half.
+fn line_pieces(mut line: &str) -> impl Iterator<Item = &str> {
+  std::iter::from_fn(move || {
+    if line.is_empty() {
+      return None;
+    }
+    let mut end = line.len();
+    if end > MAX_LINE_BYTES {
+      end = MAX_LINE_BYTES;
+      while !line.is_char_boundary(end) {
+        end -= 1;
+      }
+      if let Some(space) = line[..end].rfind(char::is_whitespace).filter(|&space| space > 0) {
+        end = space;
+      }
+    }
+    let (piece, rest) = line.split_at(end);
+    line = rest;
+    Some(piece)
+  })
+}
+
+/// How many counters `--approx-top N` keeps for each of the N words.
+const APPROX_COUNTERS_PER_WORD: usize = 10;
+
+/// A Misra-Gries summary of the words seen, which only ever holds a fixed
+/// number of counters.
+///
+/// Any word that's more than `1 / (capacity + 1)` of all the words is sure to
+/// still have a counter at the end, and each counter is at most `decrements`
+/// below that word's real count.
+struct MisraGries {
+  capacity: usize,
+  counters: HashMap<String, usize>,
+  /// How many times every counter has been knocked down by one.
+  decrements: usize,
+}
+impl MisraGries {
+  fn new(capacity: usize) -> Self {
+    Self { capacity, counters: HashMap::with_capacity(capacity), decrements: 0 }
+  }
+
+  fn add(&mut self, word: &str) {
+    if let Some(count) = self.counters.get_mut(word) {
+      *count += 1;
+    } else if self.counters.len() < self.capacity {
+      self.counters.insert(word.to_string(), 1);
+    } else {
+      self.counters.retain(|_, count| {
+        *count -= 1;
+        *count > 0
+      });
+      self.decrements += 1;
+    }
+  }
+}
+
+/// The `--external-merge` counts, which are kept in memory until there are more
+/// than `limit` different words, then saved to a spill file so that counting
+/// can start over empty. The spill files are deleted when this is dropped.
+///
+/// The spill files go in a new directory in the temp directory that only this
+/// user can get into, and each one has to be a new file, so nobody else can
+/// swap a spill file out for a symlink to some other file.
+struct Spiller {
+  limit: usize,
+  counts: BTreeMap<String, usize>,
+  dir: Option<PathBuf>,
+  files: Vec<PathBuf>,
+}
+impl Spiller {
+  fn new(limit: usize) -> Self {
+    Self { limit, counts: BTreeMap::new(), dir: None, files: Vec::new() }
+  }
+
+  fn add(&mut self, word: Cow<str>) -> std::io::Result<()> {
+    match self.counts.get_mut(&*word) {
+      Some(count) => *count += 1,
+      None => {
+        self.counts.insert(word.into_owned(), 1);
+        if self.counts.len() > self.limit {
+          return self.spill();
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// The directory the spill files go in, which is made the first time it's
+  /// needed.
+  fn spill_dir(&mut self) -> std::io::Result<PathBuf> {
+    if let Some(dir) = &self.dir {
+      return Ok(dir.clone());
+    }
+    // Note(Lokathor): Making the directory fails if anything is already there,
+    // so a name someone else took first just moves us on to the next one.
+    let mut attempt = 0;
+    loop {
+      let dir = std::env::temp_dir().join(format!(
+        "whist-spill-{pid}-{attempt}",
+        pid = std::process::id(),
+        attempt = attempt
+      ));
+      let mut builder = std::fs::DirBuilder::new();
+      #[cfg(unix)]
+      std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+      match builder.create(&dir) {
+        Ok(()) => {
+          self.dir = Some(dir.clone());
+          return Ok(dir);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+  /// Saves the counts in memory to a new spill file, and empties them out.
+  fn spill(&mut self) -> std::io::Result<()> {
+    if self.counts.is_empty() {
+      return Ok(());
+    }
+    let path = self.spill_dir()?.join(format!("{n}.bin", n = self.files.len()));
+    let f = std::fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+    // the path is kept even if the write fails, so a partial file still gets
+    // cleaned up.
+    self.files.push(path);
+    binary::write(f, self.counts.iter().map(|(word, &count)| (word.as_str(), count as u64)))?;
+    self.counts.clear();
+    Ok(())
+  }
+
+  fn remove_files(&mut self) {
+    for path in self.files.drain(..) {
+      if let Err(e) = std::fs::remove_file(&path) {
+        eprintln!("Couldn't remove spill file {path}: {e}", path = path.display(), e = e);
+      }
+    }
+    if let Some(dir) = self.dir.take() {
+      if let Err(e) = std::fs::remove_dir(&dir) {
+        eprintln!("Couldn't remove spill directory {dir}: {e}", dir = dir.display(), e = e);
+      }
+    }
+  }
+}
+impl Drop for Spiller {
+  fn drop(&mut self) {
+    self.remove_files();
+  }
+}
+
+/// Parses a duration for `flag`, such as `90s`, `30m`, `24h`, `7d`, or `2w`.
+fn parse_duration(flag: &str, s: &str) -> Duration {
+  let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+  let (number, unit) = s.split_at(split);
+  let seconds_per = match unit {
+    "s" => 1,
+    "m" => 60,
+    "h" => 60 * 60,
+    "d" => 24 * 60 * 60,
+    "w" => 7 * 24 * 60 * 60,
+    _ => {
+      eprintln!(
+        "Bad value for {flag}, `{s}`: the unit must be s, m, h, d, or w.",
+        flag = flag,
+        s = s
       );
+      std::process::exit(1);
     }
+  };
+  match parse_arg::<u64>(flag, number).checked_mul(seconds_per) {
+    Some(secs) => Duration::from_secs(secs),
+    None => {
+      eprintln!("Bad value for {flag}, `{s}`: that's too long.", flag = flag, s = s);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// Parses a `--since-date` of the form `YYYY-MM-DD`, as the start of that day
+/// in UTC.
+fn parse_date(s: &str) -> SystemTime {
+  let parts: Vec<&str> = s.split('-').collect();
+  if parts.len() != 3 {
+    eprintln!("Bad value for --since-date, `{s}`: it must be YYYY-MM-DD.", s = s);
+    std::process::exit(1);
+  }
+  let year: i64 = parse_arg("--since-date", parts[0]);
+  let month: i64 = parse_arg("--since-date", parts[1]);
+  let day: i64 = parse_arg("--since-date", parts[2]);
+  let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+  let month_length = match month {
+    2 if is_leap_year => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  };
+  if !(1..=12).contains(&month) || !(1..=month_length).contains(&day) {
+    eprintln!("Bad value for --since-date, `{s}`: that's not a real date.", s = s);
+    std::process::exit(1);
+  }
+  // Note(Lokathor): This is the `days_from_civil` algorithm from
+  // http://howardhinnant.github.io/date_algorithms.html, the reverse of what
+  // `utc_date_of` does.
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year.rem_euclid(400);
+  let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  let days = era * 146_097 + day_of_era - 719_468;
+  if days >= 0 {
+    UNIX_EPOCH + Duration::from_secs(days as u64 * 86_400)
   } else {
-    for (word, count) in word_counts.iter() {
-      println!(
-        "{word:>biggest_word$}: {count}",
-        word = word,
-        count = count,
-        biggest_word = biggest_word
-      );
+    UNIX_EPOCH - Duration::from_secs(days.unsigned_abs() * 86_400)
+  }
+}
+
+/// The (UTC) calendar year that a time falls in.
+fn year_of(time: SystemTime) -> i64 {
+  utc_date_of(time).0
+}
+
+/// The (UTC) year, month, day, and seconds into that day that a time falls
+/// on.
+fn utc_date_of(time: SystemTime) -> (i64, i64, i64, i64) {
+  let secs = match time.duration_since(UNIX_EPOCH) {
+    Ok(d) => d.as_secs() as i64,
+    Err(e) => -(e.duration().as_secs() as i64),
+  };
+  // Note(Lokathor): This is the `civil_from_days` algorithm from
+  // http://howardhinnant.github.io/date_algorithms.html. Eras are 400 year
+  // blocks starting on March 1st.
+  let days = secs.div_euclid(86_400) + 719_468;
+  let era = days.div_euclid(146_097);
+  let day_of_era = days.rem_euclid(146_097);
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let year = year_of_era + era * 400;
+  // months 10 and 11 (January and February) belong to the next year.
+  let (year, month) =
+    if month_index >= 10 { (year + 1, month_index - 9) } else { (year, month_index + 3) };
+  (year, month, day, secs.rem_euclid(86_400))
+}
+
+/// How long to wait before the first `--read-retries` retry. Each retry after
+/// that waits twice as long as the one before, up to `MAX_RETRY_DOUBLINGS`
+/// times.
+const RETRY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// How many times the `--read-retries` wait can double, so the longest wait
+/// between tries is 3.2 seconds.
+const MAX_RETRY_DOUBLINGS: u32 = 6;
+
+/// Reads the file's bytes onto the end of `buf`.
+///
+/// A failed read is tried again from the start of the file (or the `--offset`
+/// into it), up to `--read-retries` times.
+fn read_file(f: &mut std::fs::File, config: &Config, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+  let start_len = buf.len();
+  let mut retries = 0;
+  if config.offset > 0 {
+    f.seek(SeekFrom::Start(config.offset))?;
+  }
+  loop {
+    let result = match config.head_bytes {
+      Some(n) => (&mut *f).take(n).read_to_end(buf),
+      None => f.read_to_end(buf),
+    };
+    match result {
+      Err(e) if retries < config.read_retries => {
+        if config.verbose {
+          eprintln!("Read failed, trying again: {e}", e = e);
+        }
+        std::thread::sleep(RETRY_BACKOFF * 2u32.pow(retries.min(MAX_RETRY_DOUBLINGS)));
+        retries += 1;
+        buf.truncate(start_len);
+        f.seek(SeekFrom::Start(config.offset))?;
+      }
+      result => return result,
+    }
+  }
+}
+
+/// Counts how many bytes aren't part of a valid UTF-8 sequence.
+fn invalid_utf8_bytes(mut bytes: &[u8]) -> usize {
+  let mut invalid = 0;
+  while let Err(e) = std::str::from_utf8(bytes) {
+    let after_valid = &bytes[e.valid_up_to()..];
+    // no error length means that the bytes end part way into a sequence.
+    let bad = e.error_len().unwrap_or(after_valid.len());
+    invalid += bad;
+    bytes = &after_valid[bad..];
+  }
+  invalid
+}
+
+/// The length of `bytes` without any partial UTF-8 sequence at the very end.
+fn complete_utf8_len(bytes: &[u8]) -> usize {
+  // Note(Lokathor): A UTF-8 sequence is at most 4 bytes, so only the last 3
+  // bytes could be the start of an unfinished one.
+  for back in 1..=bytes.len().min(3) {
+    let i = bytes.len() - back;
+    let needed = match bytes[i] {
+      0b1100_0000..=0b1101_1111 => 2,
+      0b1110_0000..=0b1110_1111 => 3,
+      0b1111_0000..=0b1111_0111 => 4,
+      0b1000_0000..=0b1011_1111 => continue,
+      _ => return bytes.len(),
+    };
+    return if back < needed { i } else { bytes.len() };
+  }
+  bytes.len()
+}
+
+/// Writes the `--reverse-index` as a JSON object with a key for each word, whose
+/// value is an object of the path of each file the word was in and the count
+/// it had in that file, in order of path.
+fn write_reverse_index<K: WordKey>(
+  config: &Config, path: &str, reverse_index: &BTreeMap<K, Vec<(usize, usize)>>,
+  indexed_paths: &[String],
+) -> std::io::Result<()> {
+  let mut out = BufWriter::new(std::fs::File::create(path)?);
+  write!(out, "{{")?;
+  for (i, (key, files)) in reverse_index.iter().enumerate() {
+    write!(out, "{comma}\n  ", comma = if i == 0 { "" } else { "," })?;
+    serde_json::to_writer(&mut out, &shown_word(config, key.word()))?;
+    write!(out, ": {{")?;
+    let mut files: Vec<(&str, usize)> =
+      files.iter().map(|&(file, count)| (indexed_paths[file].as_str(), count)).collect();
+    files.sort_unstable();
+    for (j, (file, count)) in files.iter().enumerate() {
+      write!(out, "{comma}", comma = if j == 0 { "" } else { ", " })?;
+      serde_json::to_writer(&mut out, file)?;
+      write!(out, ": {count}", count = count)?;
     }
+    write!(out, "}}")?;
+  }
+  writeln!(out, "\n}}")?;
+  out.flush()
+}
+
+/// Writes the words and counts to a file as a JSON object.
+fn write_json<'a>(path: &str, rows: impl Iterator<Item = (&'a str, usize)>) -> std::io::Result<()> {
+  let mut out = BufWriter::new(std::fs::File::create(path)?);
+  write!(out, "{{")?;
+  for (i, (word, count)) in rows.enumerate() {
+    write!(out, "{comma}\n  ", comma = if i == 0 { "" } else { "," })?;
+    serde_json::to_writer(&mut out, word)?;
+    write!(out, ": {count}", count = count)?;
+  }
+  writeln!(out, "\n}}")?;
+  out.flush()
+}
+
+/// What a `--report-json` says about the run, besides the counts.
+struct ReportMeta {
+  files: usize,
+  tokens: usize,
+  unique_words: usize,
+  written: SystemTime,
+}
+
+fn write_report_json<'a>(
+  path: &str, meta: &ReportMeta, rows: impl Iterator<Item = (&'a str, usize)>,
+) -> std::io::Result<()> {
+  let mut out = BufWriter::new(std::fs::File::create(path)?);
+  let (year, month, day, secs) = utc_date_of(meta.written);
+  writeln!(out, "{{\n  \"meta\": {{")?;
+  writeln!(out, "    \"version\": \"{version}\",", version = env!("CARGO_PKG_VERSION"))?;
+  writeln!(
+    out,
+    "    \"timestamp\": \"{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z\",",
+    year = year,
+    month = month,
+    day = day,
+    hour = secs / 3600,
+    minute = secs / 60 % 60,
+    second = secs % 60
+  )?;
+  writeln!(out, "    \"files\": {files},", files = meta.files)?;
+  writeln!(out, "    \"tokens\": {tokens},", tokens = meta.tokens)?;
+  writeln!(out, "    \"unique_words\": {unique_words}", unique_words = meta.unique_words)?;
+  write!(out, "  }},\n  \"counts\": {{")?;
+  for (i, (word, count)) in rows.enumerate() {
+    write!(out, "{comma}\n    ", comma = if i == 0 { "" } else { "," })?;
+    serde_json::to_writer(&mut out, word)?;
+    write!(out, ": {count}", count = count)?;
+  }
+  writeln!(out, "\n  }}\n}}")?;
+  out.flush()
+}
+
+/// Prints how many of the `counts` are in each bucket.
+///
+/// If `open_top` is set there's one more bucket after the last edge, for all
+/// the counts bigger than it.
+fn print_buckets(counts: impl Iterator<Item = usize>, edges: &[usize], open_top: bool, end: char) {
+  let mut tallies = vec![0_usize; edges.len() + 1];
+  for count in counts {
+    let bucket = edges.iter().position(|&edge| count <= edge).unwrap_or(edges.len());
+    tallies[bucket] += 1;
+  }
+  let mut labels = Vec::with_capacity(tallies.len());
+  let mut low = 1;
+  for &high in edges {
+    labels.push(if low == high { format!("{}", high) } else { format!("{}-{}", low, high) });
+    low = high + 1;
+  }
+  if open_top {
+    labels.push(format!("{}+", low));
+  }
+  let biggest_label = labels.iter().map(String::len).max().unwrap_or(0);
+  for (label, tally) in labels.iter().zip(tallies.iter()) {
+    print!(
+      "{label:>biggest_label$}: {tally}{end}",
+      label = label,
+      tally = tally,
+      biggest_label = biggest_label,
+      end = end
+    );
+  }
+}
+
+/// How many bytes it would take to store the words with a simple word model:
+/// each distinct word spelled out once, plus each word seen coded in
+/// `log2(total / count)` bits, where `total` is how many words there are in
+/// all. The second part is the unigram entropy of the words, so this ignores
+/// the spaces and punctuation between them and any word order.
+fn word_model_bytes<'w>(counts: impl Iterator<Item = (&'w str, usize)> + Clone) -> f64 {
+  let total = counts.clone().map(|(_, count)| count).sum::<usize>() as f64;
+  let bits: f64 = counts
+    .filter(|&(_, count)| count > 0)
+    .map(|(word, count)| {
+      let count = count as f64;
+      8.0 * word.len() as f64 + count * (total / count).log2()
+    })
+    .sum();
+  bits / 8.0
+}
+
+/// The fraction of the chars in `word` that are alphabetic.
+fn alpha_ratio(word: &str) -> f64 {
+  let (alpha, total) =
+    word.chars().fold((0, 0), |(alpha, total), c| (alpha + c.is_alphabetic() as usize, total + 1));
+  if total == 0 {
+    0.0
+  } else {
+    alpha as f64 / total as f64
   }
 }
 
+/// The top ends of power of two buckets (`1`, `2-3`, `4-7`, and so on), going
+/// just far enough to hold `max`.
+fn log_bucket_edges(max: usize) -> Vec<usize> {
+  let mut edges: Vec<usize> = Vec::new();
+  while edges.last().map_or(max > 0, |&edge| edge < max) {
+    let low = edges.last().map_or(1, |&edge| edge + 1);
+    edges.push(low.saturating_mul(2) - 1);
+  }
+  edges
+}
+
 /// Recursively walks over the `path` given, which must be a directory.
 ///
-/// Your `op` is passed a [`PathBuf`] for each file found.
-pub fn recursive_read_dir(path: impl AsRef<Path>, mut op: impl FnMut(PathBuf)) {
+/// Your `op` is passed a [`PathBuf`] for each file found. If it gives back
+/// [`ControlFlow::Break`] the walk stops right away. Problems reading the
+/// directories are printed, and also stop the walk if `stop_on_error` is set.
+/// The walk gives back [`ControlFlow::Break`] if it stopped early.
+///
+/// If `sorted` is set then each directory's entries are visited in order of
+/// their names, so the files always come out in the same order. Otherwise the
+/// order is whatever the file system gives.
+///
+/// Symlinks are followed, but a directory that was already walked (through
+/// another symlink, or one that points back up the tree) isn't walked again.
+pub fn recursive_read_dir(
+  path: impl AsRef<Path>, sorted: bool, stop_on_error: bool,
+  mut op: impl FnMut(PathBuf) -> ControlFlow<()>,
+) -> ControlFlow<()> {
   let path = path.as_ref();
   assert!(path.is_dir());
   // Note(Lokathor): Being *literally* recursive can blow out the stack for no
@@ -102,46 +3901,211 @@ pub fn recursive_read_dir(path: impl AsRef<Path>, mut op: impl FnMut(PathBuf)) {
   // * Files get passed to the `op`
   // * Symlinks we check if they point to a Dir or File and act accordingly.
   //
-  // REMINDER: if a symlink makes a loop on the file system then this will trap
-  // us in an endless loop. That's the user's fault!
+  // Following symlinks means the same directory can be found more than once
+  // (or forever, if a symlink points back up the tree), so each directory is
+  // only walked the first time its device and inode are seen.
   let mut path_q = VecDeque::new();
+  let mut visited: HashSet<(u64, u64)> = HashSet::new();
   path_q.push_back(PathBuf::from(path));
   while let Some(path_buf) = path_q.pop_front() {
-    match std::fs::read_dir(&path_buf) {
-      Err(e) => eprintln!("Can't read_dir {path}: {e}", path = path_buf.display(), e = e),
-      Ok(read_dir) => {
-        for result_dir_entry in read_dir {
-          match result_dir_entry {
-            Err(e) => eprintln!("Error with dir entry: {e}", e = e),
-            Ok(dir_entry) => match dir_entry.file_type() {
-              Ok(ft) if ft.is_dir() => path_q.push_back(dir_entry.path()),
-              Ok(ft) if ft.is_file() => op(dir_entry.path()),
-              Ok(ft) if ft.is_symlink() => match dir_entry.metadata() {
-                Ok(metadata) if metadata.is_dir() => path_q.push_back(dir_entry.path()),
-                Ok(metadata) if metadata.is_file() => op(dir_entry.path()),
-                Err(e) => eprintln!(
-                  "Can't get metadata for symlink {path}: {e}",
-                  path = dir_entry.path().display(),
-                  e = e
-                ),
-                _ => eprintln!(
-                  "Found symlink {path} but it's not a file or a directory.",
-                  path = dir_entry.path().display()
-                ),
-              },
-              Err(e) => eprintln!(
-                "Can't get file type of {path}: {e}",
+    if file_id(&path_buf).is_some_and(|id| !visited.insert(id)) {
+      continue;
+    }
+    let read_dir = match std::fs::read_dir(&path_buf) {
+      Ok(read_dir) => read_dir,
+      Err(e) => {
+        eprintln!("Can't read_dir {path}: {e}", path = path_buf.display(), e = e);
+        if stop_on_error {
+          return ControlFlow::Break(());
+        }
+        continue;
+      }
+    };
+    let mut entries: Vec<std::io::Result<std::fs::DirEntry>> = read_dir.collect();
+    if sorted {
+      entries.sort_by_key(|entry| entry.as_ref().ok().map(|entry| entry.file_name()));
+    }
+    for result_dir_entry in entries {
+      let failed = match result_dir_entry {
+        Err(e) => {
+          eprintln!("Error with dir entry: {e}", e = e);
+          true
+        }
+        Ok(dir_entry) => match dir_entry.file_type() {
+          Ok(ft) if ft.is_dir() => {
+            path_q.push_back(dir_entry.path());
+            false
+          }
+          Ok(ft) if ft.is_file() => {
+            op(dir_entry.path())?;
+            false
+          }
+          // `DirEntry::metadata` doesn't follow symlinks, `fs::metadata` does.
+          Ok(ft) if ft.is_symlink() => match std::fs::metadata(dir_entry.path()) {
+            Ok(metadata) if metadata.is_dir() => {
+              path_q.push_back(dir_entry.path());
+              false
+            }
+            Ok(metadata) if metadata.is_file() => {
+              op(dir_entry.path())?;
+              false
+            }
+            Err(e) => {
+              eprintln!(
+                "Can't get metadata for symlink {path}: {e}",
                 path = dir_entry.path().display(),
                 e = e
-              ),
-              _ => eprintln!(
-                "Found dir_entry {path} but it's not a file, directory, or symlink.",
+              );
+              true
+            }
+            _ => {
+              eprintln!(
+                "Found symlink {path} but it's not a file or a directory.",
                 path = dir_entry.path().display()
-              ),
-            },
+              );
+              false
+            }
+          },
+          Err(e) => {
+            eprintln!(
+              "Can't get file type of {path}: {e}",
+              path = dir_entry.path().display(),
+              e = e
+            );
+            true
           }
-        }
+          _ => {
+            eprintln!(
+              "Found dir_entry {path} but it's not a file, directory, or symlink.",
+              path = dir_entry.path().display()
+            );
+            false
+          }
+        },
+      };
+      if failed && stop_on_error {
+        return ControlFlow::Break(());
       }
     }
   }
+  ControlFlow::Continue(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The config for running whist with `flags`, counting the files in `root`.
+  fn config_with(flags: &[&str], root: &Path) -> Config {
+    let args: Vec<String> =
+      std::iter::once("whist").chain(flags.iter().copied()).map(String::from).collect();
+    parse_config(&args, root.to_path_buf())
+  }
+
+  /// Counts `text` with a full run of whist with `flags`, and gives back the
+  /// counts that it writes with `--also-json`.
+  fn counted(test: &str, flags: &[&str], text: &str) -> BTreeMap<String, u64> {
+    let dir = std::env::temp_dir().join(format!(
+      "whist-test-{pid}-{test}",
+      pid = std::process::id(),
+      test = test
+    ));
+    let root = dir.join("root");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("text.txt"), text).unwrap();
+    let json = dir.join("counts.json");
+    let json_arg = json.to_string_lossy().into_owned();
+    let flags: Vec<&str> = flags.iter().copied().chain(["--also-json", &json_arg]).collect();
+    assert!(run(&config_with(&flags, &root), &mut HashSet::new()).is_ok());
+    let counts = serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    counts
+  }
+
+  fn counts(rows: &[(&str, u64)]) -> BTreeMap<String, u64> {
+    rows.iter().map(|&(word, count)| (word.to_string(), count)).collect()
+  }
+
+  #[test]
+  fn intern_word_gives_back_the_same_copy() {
+    let mut intern = HashSet::new();
+    let first = intern_word(&mut intern, "apple");
+    let again = intern_word(&mut intern, &String::from("apple"));
+    assert!(std::ptr::eq(first, again));
+    assert!(!std::ptr::eq(first, intern_word(&mut intern, "apply")));
+    assert_eq!(intern.len(), 2);
+  }
+
+  #[test]
+  fn transform_word_does_every_transform() {
+    let root = Path::new(".");
+    let config = config_with(
+      &["--transliterate", "--strip-prefix", "re", "--mask-digits", "#", "--fold-confusables"],
+      root,
+    );
+    let mut intern = HashSet::new();
+    let mut spellings = Spellings::new();
+    let mut transform = |word: &str| {
+      transform_word(&config, &mut intern, &mut spellings, Cow::Borrowed(word)).map(Cow::into_owned)
+    };
+    assert_eq!(transform("récafé42").as_deref(), Some("cafe#"));
+    assert_eq!(transform("\u{301}"), None);
+    // `modern` and `rnodern` look alike, so the first one seen is used for both.
+    assert_eq!(transform("modern").as_deref(), Some("modern"));
+    assert_eq!(transform("rnodern").as_deref(), Some("modern"));
+    assert_eq!(transform("rerun").as_deref(), Some("run"));
+  }
+
+  #[test]
+  fn case_folded_words_count_together() {
+    let text = "Apple apple APPLE pear";
+    assert_eq!(counted("case", &[], text), counts(&[("Apple", 3), ("pear", 1)]));
+    assert_eq!(
+      counted("case-sensitive", &["--case-sensitive"], text),
+      counts(&[("APPLE", 1), ("Apple", 1), ("apple", 1), ("pear", 1)])
+    );
+  }
+
+  #[test]
+  fn masked_digits_count_together() {
+    let text = "v1 v2 v10 v1_2 v";
+    assert_eq!(
+      counted("mask", &["--mask-digits", "#"], text),
+      counts(&[("v", 1), ("v#", 3), ("v#_#", 1)])
+    );
+  }
+
+  #[test]
+  fn stripped_prefixes_count_together() {
+    let text = "undo redo do Redo";
+    let flags = ["--strip-prefix", "un", "--strip-prefix", "re"];
+    assert_eq!(counted("prefix", &flags, text), counts(&[("do", 4)]));
+    let flags = ["--strip-prefix", "un", "--strip-prefix", "re", "--case-sensitive"];
+    assert_eq!(counted("prefix-sensitive", &flags, text), counts(&[("Redo", 1), ("do", 3)]));
+  }
+
+  #[test]
+  fn transliterated_words_count_together() {
+    let text = "café cafe Café";
+    assert_eq!(counted("transliterate", &["--transliterate"], text), counts(&[("cafe", 3)]));
+  }
+
+  #[test]
+  fn confusable_words_count_together() {
+    // the first letter of the first word is a Cyrillic `а`.
+    let text = "\u{430}pple apple apply modern rnodern";
+    assert_eq!(
+      counted("confusables", &["--fold-confusables", "--case-sensitive"], text),
+      counts(&[("\u{430}pple", 2), ("apply", 1), ("modern", 2)])
+    );
+  }
+
+  #[test]
+  fn shapes_count_together() {
+    let text = "Cat Dog cat HTTP";
+    assert_eq!(
+      counted("shapes", &["--shapes"], text),
+      counts(&[("Xxx", 2), ("xxx", 1), ("XXXX", 1)])
+    );
+  }
 }
@@ -0,0 +1,142 @@
+//! The `--interactive` mode, for looking at the counted words different ways
+//! without counting them all over again.
+
+use std::io::{BufRead, Write};
+
+/// The commands, as printed by `help`.
+const HELP: &str = "\
+top N       only show the N first words (`top` alone shows them all again)
+prefix P    only show words starting with P (`prefix` alone shows them all again)
+min N       only show words counted at least N times
+sort freq   show the most frequent words first
+sort alpha  show the words in alphabetical order
+reset       go back to showing every word
+help        show this list
+quit        stop (so does end of input)";
+
+/// Reads commands from stdin, printing the words that pass the filters after
+/// each one.
+///
+/// The `alphabetical` list must already be in alphabetical order, and the view
+/// starts in frequency order if `by_frequency` is set. Nothing is printed
+/// until the first command, since the full list could be very long.
+///
+/// A `prefix` matches in any case unless `case_sensitive` is set.
+pub fn explore(
+  alphabetical: Vec<(&'static str, usize)>, by_frequency: bool, case_sensitive: bool,
+) -> std::io::Result<()> {
+  let Listing { alphabetical, frequency, biggest_word } = Listing::new(alphabetical);
+  let mut filters = Filters { by_frequency, ..Filters::default() };
+  let stdin = std::io::stdin();
+  let stdout = std::io::stdout();
+  let mut out = stdout.lock();
+  writeln!(
+    out,
+    "{words} words counted, type `help` for the commands.",
+    words = alphabetical.len()
+  )?;
+  let mut lines = stdin.lock().lines();
+  loop {
+    write!(out, "> ")?;
+    out.flush()?;
+    let line = match lines.next() {
+      Some(line) => line?,
+      None => return Ok(()),
+    };
+    let mut parts = line.split_whitespace();
+    let command = match parts.next() {
+      Some(command) => command,
+      None => continue,
+    };
+    let arg = parts.next();
+    match (command, arg) {
+      ("quit", _) | ("exit", _) => return Ok(()),
+      ("help", _) => {
+        writeln!(out, "{help}", help = HELP)?;
+        continue;
+      }
+      ("top", None) => filters.top = None,
+      ("prefix", None) => filters.prefix = None,
+      ("prefix", Some(prefix)) if case_sensitive => filters.prefix = Some(prefix.to_string()),
+      ("prefix", Some(prefix)) => filters.prefix = Some(prefix.to_lowercase()),
+      ("sort", Some("freq")) => filters.by_frequency = true,
+      ("sort", Some("alpha")) => filters.by_frequency = false,
+      ("reset", _) => {
+        filters = Filters { by_frequency: filters.by_frequency, ..Filters::default() }
+      }
+      ("top", Some(n)) | ("min", Some(n)) => match n.parse() {
+        Ok(n) if command == "top" => filters.top = Some(n),
+        Ok(n) => filters.min = n,
+        Err(_) => {
+          writeln!(out, "`{n}` isn't a number.", n = n)?;
+          continue;
+        }
+      },
+      _ => {
+        writeln!(
+          out,
+          "Unknown command `{line}`, type `help` for the commands.",
+          line = line.trim()
+        )?;
+        continue;
+      }
+    }
+    let rows = if filters.by_frequency { &frequency } else { &alphabetical };
+    let shown = rows
+      .iter()
+      .filter(|&&(word, count)| count >= filters.min && filters.keeps(word, case_sensitive))
+      .take(filters.top.unwrap_or(usize::MAX));
+    let mut any_shown = false;
+    for (word, count) in shown {
+      any_shown = true;
+      writeln!(
+        out,
+        "{word:>biggest_word$}: {count}",
+        word = word,
+        count = count,
+        biggest_word = biggest_word
+      )?;
+    }
+    if !any_shown {
+      writeln!(out, "No words pass the filters.")?;
+    }
+  }
+}
+
+#[derive(Default)]
+struct Filters {
+  top: Option<usize>,
+  /// Already lowercase, unless it's case sensitive.
+  prefix: Option<String>,
+  min: usize,
+  by_frequency: bool,
+}
+
+impl Filters {
+  fn keeps(&self, word: &str, case_sensitive: bool) -> bool {
+    match &self.prefix {
+      Some(prefix) if case_sensitive => word.starts_with(prefix.as_str()),
+      Some(prefix) => word.to_lowercase().starts_with(prefix.as_str()),
+      None => true,
+    }
+  }
+}
+
+/// The words in both of the orders that a view can switch between.
+pub struct Listing {
+  pub alphabetical: Vec<(&'static str, usize)>,
+  /// Most frequent first, with ties in alphabetical order.
+  pub frequency: Vec<(&'static str, usize)>,
+  /// How many chars the longest word is, for lining up the counts.
+  pub biggest_word: usize,
+}
+
+impl Listing {
+  /// The `alphabetical` list must already be in alphabetical order.
+  pub fn new(alphabetical: Vec<(&'static str, usize)>) -> Self {
+    let mut frequency = alphabetical.clone();
+    frequency.sort_by(|(w1, c1), (w2, c2)| c2.cmp(c1).then_with(|| w1.cmp(w2)));
+    let biggest_word = alphabetical.iter().map(|(w, _)| w.chars().count()).max().unwrap_or(0);
+    Listing { alphabetical, frequency, biggest_word }
+  }
+}
@@ -0,0 +1,158 @@
+//! The `--tui` mode, for browsing through the counted words.
+
+use ratatui::{
+  crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+  layout::{Constraint, Layout},
+  style::{Modifier, Style},
+  text::Line,
+  widgets::{Block, Paragraph},
+  DefaultTerminal, Frame,
+};
+
+use crate::repl::Listing;
+
+/// Opens up a scrollable and filterable view of the words given.
+///
+/// The `alphabetical` list must already be in alphabetical order, and the view
+/// starts in frequency order if `by_frequency` is set.
+///
+/// * Typing filters the words down to those containing the filter text.
+/// * Tab switches between alphabetical and frequency order.
+/// * Arrow keys, Page Up/Down, and Home/End scroll.
+/// * Esc or Ctrl+C quits.
+pub fn browse(alphabetical: Vec<(&'static str, usize)>, by_frequency: bool) -> std::io::Result<()> {
+  let Listing { alphabetical, frequency, biggest_word } = Listing::new(alphabetical);
+  let mut browser = Browser {
+    alphabetical,
+    frequency,
+    biggest_word,
+    by_frequency,
+    filter: String::new(),
+    shown: Vec::new(),
+    selected: 0,
+    offset: 0,
+  };
+  browser.apply_filter();
+  ratatui::run(|terminal| browser.run(terminal))
+}
+
+struct Browser {
+  alphabetical: Vec<(&'static str, usize)>,
+  frequency: Vec<(&'static str, usize)>,
+  biggest_word: usize,
+  by_frequency: bool,
+  filter: String,
+  /// Indexes of the rows that pass the filter, in the current order.
+  shown: Vec<usize>,
+  /// Index into `shown` of the highlighted row.
+  selected: usize,
+  /// Index into `shown` of the top row on screen.
+  offset: usize,
+}
+
+impl Browser {
+  fn rows(&self) -> &[(&'static str, usize)] {
+    if self.by_frequency {
+      &self.frequency
+    } else {
+      &self.alphabetical
+    }
+  }
+
+  fn apply_filter(&mut self) {
+    let filter = self.filter.to_lowercase();
+    self.shown = self
+      .rows()
+      .iter()
+      .enumerate()
+      .filter(|(_, (word, _))| filter.is_empty() || word.to_lowercase().contains(&filter))
+      .map(|(i, _)| i)
+      .collect();
+    self.selected = 0;
+    self.offset = 0;
+  }
+
+  fn run(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+    loop {
+      let mut page = 0;
+      terminal.draw(|frame| page = self.render(frame))?;
+      if let Event::Key(key) = event::read()? {
+        if key.kind != KeyEventKind::Press {
+          continue;
+        }
+        let last = self.shown.len().saturating_sub(1);
+        match key.code {
+          KeyCode::Esc => return Ok(()),
+          KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+          KeyCode::Tab => {
+            self.by_frequency = !self.by_frequency;
+            self.apply_filter();
+          }
+          KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+          KeyCode::Down => self.selected = (self.selected + 1).min(last),
+          KeyCode::PageUp => self.selected = self.selected.saturating_sub(page),
+          KeyCode::PageDown => self.selected = (self.selected + page).min(last),
+          KeyCode::Home => self.selected = 0,
+          KeyCode::End => self.selected = last,
+          KeyCode::Backspace => {
+            self.filter.pop();
+            self.apply_filter();
+          }
+          KeyCode::Char(c) => {
+            self.filter.push(c);
+            self.apply_filter();
+          }
+          _ => (),
+        }
+      }
+    }
+  }
+
+  /// Draws the view, giving back how many rows fit on screen.
+  fn render(&mut self, frame: &mut Frame) -> usize {
+    let [list_area, filter_area] =
+      Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    let order = if self.by_frequency { "frequency" } else { "alphabetical" };
+    let block = Block::bordered().title(format!(
+      " {shown} of {total} words, {order} order (Tab to switch) ",
+      shown = self.shown.len(),
+      total = self.alphabetical.len(),
+      order = order
+    ));
+    let page = block.inner(list_area).height as usize;
+    // keep the selected row on screen.
+    if self.selected < self.offset {
+      self.offset = self.selected;
+    } else if page > 0 && self.selected >= self.offset + page {
+      self.offset = self.selected + 1 - page;
+    }
+    let rows = self.rows();
+    let lines: Vec<Line> = self
+      .shown
+      .iter()
+      .enumerate()
+      .skip(self.offset)
+      .take(page)
+      .map(|(i, &row)| {
+        let (word, count) = rows[row];
+        let line = Line::from(format!(
+          "{word:>biggest_word$}: {count}",
+          word = word,
+          count = count,
+          biggest_word = self.biggest_word
+        ));
+        if i == self.selected {
+          line.style(Style::new().add_modifier(Modifier::REVERSED))
+        } else {
+          line
+        }
+      })
+      .collect();
+    frame.render_widget(Paragraph::new(lines).block(block), list_area);
+    frame.render_widget(
+      Paragraph::new(format!("filter: {filter}", filter = self.filter)),
+      filter_area,
+    );
+    page
+  }
+}
@@ -0,0 +1,93 @@
+//! The `--wordcloud` output, a simple SVG word cloud.
+
+use std::{
+  fmt::Write as _,
+  io::{self, Write},
+};
+
+/// The font size of the least frequent words, in pixels.
+const MIN_FONT: f64 = 12.0;
+/// The font size of the most frequent word, in pixels.
+const MAX_FONT: f64 = 72.0;
+
+/// Writes an SVG word cloud of the words given to `path`.
+///
+/// The words should be most frequent first, since each word is placed as
+/// close to the middle as it can go without covering a word placed before it.
+/// Each word's font size scales with the square root of its count, so that
+/// its area scales with the count.
+///
+/// There's no real font measuring involved, each word's size is estimated
+/// from how many chars it has, so words can sometimes overlap a little.
+pub fn write_svg(path: &str, words: &[(&str, usize)]) -> io::Result<()> {
+  let max_count = words.iter().map(|&(_, count)| count).max().unwrap_or(1) as f64;
+  // each word, its font size, and where the middle of its box is.
+  let mut placed: Vec<(&str, f64, f64, f64)> = Vec::new();
+  // the middle of each placed word's box, and its half width and half height.
+  let mut boxes: Vec<(f64, f64, f64, f64)> = Vec::new();
+  for &(word, count) in words {
+    let size = MIN_FONT + (MAX_FONT - MIN_FONT) * (count as f64 / max_count).sqrt();
+    let (half_w, half_h) = (0.3 * size * word.chars().count() as f64, 0.5 * size);
+    // walk out along a spiral from the middle until the word fits.
+    let spot = (0..20_000).map(|step| step as f64 * 0.1).find_map(|t| {
+      let (x, y) = (2.0 * t * t.cos(), 1.5 * t * t.sin());
+      let overlaps = boxes
+        .iter()
+        .any(|&(bx, by, bw, bh)| (x - bx).abs() < half_w + bw && (y - by).abs() < half_h + bh);
+      if overlaps {
+        None
+      } else {
+        Some((x, y))
+      }
+    });
+    if let Some((x, y)) = spot {
+      placed.push((word, size, x, y));
+      boxes.push((x, y, half_w, half_h));
+    }
+  }
+
+  let margin = 10.0;
+  let (mut left, mut top, mut right, mut bottom) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+  for &(x, y, w, h) in boxes.iter() {
+    left = left.min(x - w);
+    right = right.max(x + w);
+    top = top.min(y - h);
+    bottom = bottom.max(y + h);
+  }
+  let mut svg = String::new();
+  let _ = writeln!(
+    svg,
+    r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{x:.1} {y:.1} {w:.1} {h:.1}">"#,
+    x = left - margin,
+    y = top - margin,
+    w = right - left + 2.0 * margin,
+    h = bottom - top + 2.0 * margin
+  );
+  for (word, size, x, y) in placed {
+    let _ = writeln!(
+      svg,
+      r#"<text x="{x:.1}" y="{y:.1}" font-size="{size:.1}" font-family="sans-serif" text-anchor="middle" dominant-baseline="central">{word}</text>"#,
+      x = x,
+      y = y,
+      size = size,
+      word = escape_xml(word)
+    );
+  }
+  svg.push_str("</svg>\n");
+  std::fs::File::create(path)?.write_all(svg.as_bytes())
+}
+
+fn escape_xml(word: &str) -> String {
+  let mut out = String::with_capacity(word.len());
+  for c in word.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&apos;"),
+      c => out.push(c),
+    }
+  }
+  out
+}